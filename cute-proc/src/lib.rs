@@ -0,0 +1,119 @@
+//! A procedural macro version of `cute`'s `c!` comprehension macro.
+//!
+//! The syntax and output match the list and hashmap forms of the declarative `c!`, but
+//! malformed invocations are reported on the exact token at fault, which lets editors
+//! underline the mistake instead of the whole macro call.
+//!
+//! ```rust
+//! use cute_proc::c;
+//!
+//! let squares = c![x * x, for x in 0..5, if x % 2 == 0];
+//! assert_eq!(squares, vec![0, 4, 16]);
+//!
+//! let lengths = c!{w => w.len(), for w in vec!["a", "bb"]};
+//! assert_eq!(lengths["bb"], 2);
+//! ```
+//!
+//! The results are always a `std::vec::Vec` or a `std::collections::HashMap`, even when
+//! `cute`'s `hashbrown` or `use_alloc` feature makes its `c!` return `hashbrown` maps.
+//! `cute` re-exports this macro as `cute::proc_c!` behind its `proc` feature.
+//!
+//! As with the declarative macro, the `for` clauses nest with the first clause as the
+//! innermost loop and the last one as the outermost, in both the list and the map form.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Expr, Pat, Token};
+
+enum Output {
+    List(Expr),
+    Map(Expr, Expr),
+}
+
+struct ForClause {
+    pat: Pat,
+    iter: Expr,
+}
+
+struct Comprehension {
+    output: Output,
+    fors: Vec<ForClause>,
+    conds: Vec<Expr>,
+}
+
+impl Parse for Comprehension {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() || input.peek(Token![for]) {
+            return Err(input.error("missing expression, expected `c![expression, for pattern in iterator]`"));
+        }
+
+        let first: Expr = input.parse()?;
+        let output = if input.peek(Token![=>]) {
+            input.parse::<Token![=>]>()?;
+            Output::Map(first, input.parse()?)
+        } else {
+            Output::List(first)
+        };
+
+        let mut fors = Vec::new();
+        let mut conds = Vec::new();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.peek(Token![for]) {
+                let for_token = input.parse::<Token![for]>()?;
+                if !conds.is_empty() {
+                    return Err(syn::Error::new(for_token.span, "`for` clauses must come before the `if` clauses"));
+                }
+                let pat = Pat::parse_multi_with_leading_vert(input)?;
+                input.parse::<Token![in]>()?;
+                let iter = input.parse()?;
+                fors.push(ForClause { pat, iter });
+            } else if input.peek(Token![if]) {
+                input.parse::<Token![if]>()?;
+                conds.push(input.parse()?);
+            } else {
+                return Err(input.error("expected `for pattern in iterator` or `if condition`"));
+            }
+        }
+
+        if fors.is_empty() {
+            return Err(input.error("expected a `, for pattern in iterator` clause"));
+        }
+
+        Ok(Comprehension { output, fors, conds })
+    }
+}
+
+/// List and hashmap comprehensions, with the same syntax as `cute::c!`.
+#[proc_macro]
+pub fn c(input: TokenStream) -> TokenStream {
+    let Comprehension { output, fors, conds } = parse_macro_input!(input as Comprehension);
+    let result = Ident::new("r", Span::mixed_site());
+
+    let (init, add) = match output {
+        Output::List(exp) => (quote!(::std::vec::Vec::new()), quote!(#result.push(#exp);)),
+        Output::Map(key, val) => (
+            quote!(::std::collections::HashMap::new()),
+            quote!(#result.insert(#key, #val);),
+        ),
+    };
+
+    let mut body = if conds.is_empty() {
+        add
+    } else {
+        quote!(if #((#conds))&&* { #add })
+    };
+    for ForClause { pat, iter } in &fors {
+        body = quote!(for #pat in #iter { #body });
+    }
+
+    TokenStream::from(quote!({
+        let mut #result = #init;
+        #body
+        #result
+    }))
+}
@@ -0,0 +1,88 @@
+#[macro_use(c)]
+extern crate cute;
+
+#[test]
+fn list_comprehensions_match() {
+    assert_eq!(cute_proc::c![x * x, for x in 0..10], c![x * x, for x in 0..10]);
+    assert_eq!(cute_proc::c![x, for x in 0..10, if x % 3 == 0], c![x, for x in 0..10, if x % 3 == 0]);
+    assert_eq!(
+        cute_proc::c![x, for x in 0..20, if x % 2 == 0 || x % 3 == 0, if x > 5],
+        c![x, for x in 0..20, if x % 2 == 0 || x % 3 == 0, if x > 5]
+    );
+}
+
+#[test]
+fn nested_comprehensions_match() {
+    let nested = vec![vec![1, 2, 3], vec![4, 5, 6]];
+    assert_eq!(cute_proc::c![x, for x in y, for y in nested.clone()], c![x, for x in y, for y in nested.clone()]);
+    assert_eq!(
+        cute_proc::c![x, for x in y.iter(), for y in &nested, if *x % 2 == 0],
+        c![x, for x in y.iter(), for y in &nested, if *x % 2 == 0]
+    );
+
+    let n = 20;
+    assert_eq!(
+        cute_proc::c![(a, b, c), for c in b..n, for b in a..n, for a in 1..n, if a * a + b * b == c * c],
+        c![(a, b, c), for c in b..n, for b in a..n, for a in 1..n, if a * a + b * b == c * c]
+    );
+    assert_eq!(
+        cute_proc::c![a + b + c + d, for a in 0..3, for b in 0..3, for c in 0..3, for d in 0..3],
+        c![a + b + c + d, for a in 0..3, for b in 0..3, for c in 0..3, for d in 0..3]
+    );
+}
+
+#[test]
+fn pattern_comprehensions_match() {
+    let pairs = vec![(1, 'a'), (2, 'b'), (3, 'c')];
+    assert_eq!(cute_proc::c![c, for (n, c) in pairs.clone(), if n != 2], c![c, for (n, c) in pairs, if n != 2]);
+}
+
+#[test]
+fn hashmap_comprehensions_match() {
+    fn sorted<K: Ord, V: Ord>(map: impl IntoIterator<Item = (K, V)>) -> Vec<(K, V)> {
+        let mut v: Vec<(K, V)> = map.into_iter().collect();
+        v.sort();
+        v
+    }
+
+    assert_eq!(
+        sorted(cute_proc::c!{k => k * k, for k in 0..10}),
+        sorted(c!{k => k * k, for k in 0..10})
+    );
+    assert_eq!(
+        sorted(cute_proc::c!{k => v, for (k, v) in [("a", 1), ("b", 2)], if v > 1}),
+        sorted(c!{k => v, for (k, v) in [("a", 1), ("b", 2)], if v > 1})
+    );
+
+    let rows = vec![vec![1, 2], vec![3, 4]];
+    assert_eq!(
+        sorted(cute_proc::c!{x % 2 => x, for &x in row, for row in &rows}),
+        sorted(c!{x % 2 => x, for &x in row, for row in &rows})
+    );
+}
+
+#[test]
+fn result_binding_does_not_clash() {
+    let r = 2;
+    assert_eq!(cute_proc::c![x * r, for x in 0..3], vec![0, 2, 4]);
+}
+
+#[test]
+fn malformed_comprehensions() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
+
+#[test]
+fn outer_variable_in_inner_iterator() {
+    assert_eq!(cute_proc::c![x, for x in 0..y, for y in 1..=5], c![x, for x in 0..y, for y in 1..=5]);
+}
+
+#[test]
+fn three_level_order_matches() {
+    let nested3 = vec![vec![vec![1, 2], vec![3]], vec![vec![4]]];
+    assert_eq!(
+        cute_proc::c![x, for x in y, for y in z, for z in nested3.clone()],
+        c![x, for x in y, for y in z, for z in nested3.clone()]
+    );
+}
@@ -0,0 +1,5 @@
+use cute_proc::c;
+
+fn main() {
+    let _ = c![x, if x > 2, for x in 0..10];
+}
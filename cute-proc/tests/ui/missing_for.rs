@@ -0,0 +1,5 @@
+use cute_proc::c;
+
+fn main() {
+    let _ = c![x * x, x in 0..10];
+}
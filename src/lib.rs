@@ -1,4 +1,5 @@
 #![doc(html_logo_url = "https://raw.githubusercontent.com/mattgathu/cute/master/C!.png")]
+#![cfg_attr(all(feature = "use_alloc", not(test)), no_std)]
 //! A Macro for python-esque list and dictionary(hashmap) comprehensions in Rust
 //!
 //! The `c!` macro implements list and hashmap comprehensions similar to those found in Python,
@@ -53,6 +54,28 @@
 //! # }
 //! ```
 //!
+//! Chained conditions must all hold
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let middle = c![x, for x in 0..10, if x > 2, if x < 8];
+//! assert_eq!(middle, vec![3, 4, 5, 6, 7]);
+//! # }
+//! ```
+//!
+//! Capacity hint for the output `Vec`
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let squares = c![x*x, for x in 0..1000; 1000];
+//! assert!(squares.capacity() >= 1000);
+//! # }
+//! ```
+//!
 //! Nested Comprehensions
 //!
 //! ```rust
@@ -75,6 +98,200 @@
 //! # }
 //! ```
 //!
+//! The first `for` clause is the innermost loop and the last one the outermost, for any
+//! number of clauses, whether or not `if` clauses follow them, and in the map form too.
+//! There is deliberately no form that nests the clauses in written order, as Python does,
+//! so that every comprehension reads the same way. The inner iterator can be built from
+//! the outer loop's variable, which makes the first clause a flat map over the others.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! fn divisors(n: u32) -> Vec<u32> {
+//!     (1..=n).filter(|d| n % d == 0).collect()
+//! }
+//!
+//! let all_divisors = c![d, for d in divisors(n), for n in 4..7];
+//! assert_eq!(all_divisors, vec![1, 2, 4, 1, 5, 1, 2, 3, 6]);
+//! # }
+//! ```
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let n = 20;
+//! let triples = c![(a, b, c), for c in b..n, for b in a..n, for a in 1..n, if a * a + b * b == c * c];
+//! assert_eq!(triples, vec![(3, 4, 5), (5, 12, 13), (6, 8, 10), (8, 15, 17), (9, 12, 15)]);
+//! # }
+//! ```
+//!
+//! A guard placed between the two `for` clauses is checked in the outer loop, so the
+//! inner loop is skipped entirely for outer items that fail it.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let nested = vec![vec![1, 2, 3], vec![4, 5], vec![6, 7, 8, 9]];
+//! let long_rows: Vec<usize> = c![x, for x in y, if y.len() > 2, for y in nested];
+//! assert_eq!(long_rows, vec![1, 2, 3, 6, 7, 8, 9]);
+//! # }
+//! ```
+//!
+//! Destructuring Patterns
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! struct Point { x: i32, y: i32 }
+//!
+//! let points = vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }];
+//! let sums = c![x + y, for Point { x, y } in points];
+//! assert_eq!(sums, vec![3, 7]);
+//!
+//! struct Meters(f64);
+//!
+//! let measurements = vec![Meters(1.5), Meters(2.0)];
+//! let doubled = c![x * 2.0, for Meters(x) in measurements];
+//! assert_eq!(doubled, vec![3.0, 4.0]);
+//!
+//! let pairs = vec![(1, 'a'), (2, 'b'), (3, 'c')];
+//! let chars = c![ch, for (n, ch) in pairs, if n != 2];
+//! assert_eq!(chars, vec!['a', 'c']);
+//!
+//! // `ref` borrows each element instead of moving it into the binding
+//! let names = vec![String::from("ann"), String::from("bo")];
+//! let lengths = c![name.len(), for ref name in names];
+//! assert_eq!(lengths, vec![3, 2]);
+//! # }
+//! ```
+//!
+//! Enumerated Comprehensions
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let v = vec!["a", "b", "c", "d"];
+//! let odd: Vec<(usize, &str)> = c![(i, *x), for (i, x) in enumerate v.iter(), if i % 2 == 1];
+//! assert_eq!(odd, vec![(1, "b"), (3, "d")]);
+//!
+//! // the same thing, with an explicit `.enumerate()`
+//! let odd: Vec<(usize, &str)> = c![x, for x in v.into_iter().enumerate(), if x.0 % 2 == 1];
+//! assert_eq!(odd, vec![(1, "b"), (3, "d")]);
+//! # }
+//! ```
+//!
+//! Zipped Comprehensions
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let pairs = c![(x, y), for x in 0..5, zip y in 'a'..='c'];
+//! assert_eq!(pairs, vec![(0, 'a'), (1, 'b'), (2, 'c')]);
+//!
+//! let triples = c![(x, y, z), for x in 0..5, zip y in 'a'..='c', zip z in vec![true, false]];
+//! assert_eq!(triples, vec![(0, 'a', true), (1, 'b', false)]);
+//! # }
+//! ```
+//!
+//! Chaining several sources into one
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let head = vec![1, 2];
+//! let tail = vec![7, 8];
+//! let v = c![x * 10, for x in chain(head, 3..5, tail), if x != 8];
+//! assert_eq!(v, vec![10, 20, 30, 40, 70]);
+//! # }
+//! ```
+//!
+//! Stopping early with `while`, or skipping a prefix with `skip_while`
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let v = vec![1, 3, 5, 6, 7, 9];
+//! let leading_odd = c![x, for x in v.clone(), while x % 2 == 1];
+//! assert_eq!(leading_odd, vec![1, 3, 5]);
+//!
+//! let rest = c![x, for x in v, skip_while x % 2 == 1];
+//! assert_eq!(rest, vec![6, 7, 9]);
+//! # }
+//! ```
+//!
+//! Draining a source that is not an `Iterator` with `while let`
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let mut stack = vec![1, 2, 3, 4];
+//! let popped = c![x * 10, while let Some(x) = stack.pop(), if x != 2];
+//! assert_eq!(popped, vec![40, 30, 10]);
+//! # }
+//! ```
+//!
+//! Pattern matching with `if let`
+//!
+//! Patterns in a `for` clause must be irrefutable, so refutable patterns such as
+//! enum variants go in an `if let` clause instead.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let v = vec!["1", "two", "3"];
+//! let parsed: Vec<i32> = c![n, for s in v, if let Ok(n) = s.parse()];
+//! assert_eq!(parsed, vec![1, 3]);
+//!
+//! enum Shape { Circle(f64), Square(f64) }
+//!
+//! let shapes = vec![Shape::Circle(1.0), Shape::Square(2.0), Shape::Circle(3.0)];
+//! let radii = c![r, for shape in shapes, if let Shape::Circle(r) = shape];
+//! assert_eq!(radii, vec![1.0, 3.0]);
+//!
+//! // `if let` can come after other `if` clauses, and later clauses see its bindings
+//! let v = vec![Some(1), None, Some(2), Some(3)];
+//! let odd: Vec<i32> = c![n, for x in v, if x != Some(3), if let Some(n) = x, if n % 2 == 1];
+//! assert_eq!(odd, vec![1]);
+//! # }
+//! ```
+//!
+//! Intermediate values, computed once per element
+//!
+//! `let` clauses bind values that the following `if` clauses and the expression can use.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let words = vec!["apple", "kiwi", "banana"];
+//! let v = c![(w, len), for w in words, let len = w.len(), if len > 4];
+//! assert_eq!(v, vec![("apple", 5), ("banana", 6)]);
+//! # }
+//! ```
+//!
+//! A `let`-`else` clause binds the pattern for the rest of the comprehension and skips
+//! the elements that do not match it.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let results: Vec<Result<i32, String>> = vec![Ok(1), Err("bad".into()), Ok(3)];
+//! let good = c![n * 10, for r in results, let Ok(n) = r, else continue];
+//! assert_eq!(good, vec![10, 30]);
+//! # }
+//! ```
+//!
 //! Comprehensions over Iterators
 //!
 //! ```rust
@@ -92,11 +309,118 @@
 //!
 //! # fn main() {
 //! let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
-//! let output: Vec<i32> = c![x, for x in vec.iter(), if *x >= 0i32];
+//! let output: Vec<i32> = c![*x, for x in vec.iter(), if *x >= 0i32];
 //! assert_eq!(output, vec![0, 2, 4]);
 //! # }
 //! ```
 //!
+//! Iterating in sorted order, with the `sorted` and `sorted_by` helpers. The source is
+//! sorted before any filtering or transformation is applied.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//! use cute::{sorted, sorted_by};
+//!
+//! # fn main() {
+//! let v = c![x * x, for x in sorted(vec![3, -4, 1]), if x < 3];
+//! assert_eq!(v, vec![16, 1]);
+//!
+//! let v = c![x, for x in sorted_by(vec![3, -4, 1], |a, b| b.cmp(a))];
+//! assert_eq!(v, vec![3, 1, -4]);
+//! # }
+//! ```
+//!
+//! Keeping only the `Some` values of an `Option` expression
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let numbers = c![? s.parse::<i32>().ok(), for s in "1 two 3 four 5".split(' ')];
+//! assert_eq!(numbers, vec![1, 3, 5]);
+//! # }
+//! ```
+//!
+//! Collecting every `Result`, errors included. Unlike `try_c!`, which stops at the
+//! first error, each element keeps its own outcome; `partition_results_c!` separates
+//! the successes from the failures.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let results = c![s.parse::<i32>(), for s in vec!["1", "two", "3"]];
+//! assert_eq!(results.len(), 3);
+//! assert!(results[1].is_err());
+//! # }
+//! ```
+//!
+//! Comprehensions over strings
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let text = "  first line\nsecond line  ";
+//! let lines = c![line.trim().to_string(), for line in text.lines()];
+//! assert_eq!(lines, vec!["first line", "second line"]);
+//!
+//! let initials = c![w.chars().next().unwrap(), for w in text.split_whitespace()];
+//! assert_eq!(initials, vec!['f', 'l', 's', 'l']);
+//! # }
+//! ```
+//!
+//! Comprehensions over hashmap entries
+//!
+//! A map can be consumed directly, yielding owned `(key, value)` pairs, or borrowed
+//! with `iter()` (yielding `(&K, &V)`) and `iter_mut()` (yielding `(&K, &mut V)`).
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//! # use std::collections::HashMap;
+//!
+//! # fn main() {
+//! let mut stock: HashMap<&str, u32> = HashMap::new();
+//! stock.insert("apples", 3);
+//! stock.insert("pears", 0);
+//!
+//! let empty = c![*name, for (name, count) in stock.iter(), if *count == 0];
+//! assert_eq!(empty, vec!["pears"]);
+//!
+//! let _ = c![*count += 10, for (_, count) in stock.iter_mut()];
+//! assert_eq!(stock["pears"], 10);
+//!
+//! // consumes the map
+//! let mut names = c![name, for (name, _) in stock];
+//! names.sort();
+//! assert_eq!(names, vec!["apples", "pears"]);
+//! # }
+//! ```
+//!
+//! Sliding windows over a slice
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let readings = [1, 3, 6, 10];
+//! let deltas = c![w[1] - w[0], for w in readings.windows(2)];
+//! assert_eq!(deltas, vec![2, 3, 4]);
+//! # }
+//! ```
+//!
+//! Stepping through a range
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let every_third = c![x, for x in 0..10 step 3];
+//! assert_eq!(every_third, vec![0, 3, 6, 9]);
+//! assert_eq!(every_third, c![x, for x in (0..10).step_by(3)]);
+//! # }
+//! ```
+//!
 //! Function Application
 //!
 //! ```rust
@@ -123,11 +447,95 @@
 //! # }
 //! ```
 //!
+//! Block expressions, for factoring out intermediate values
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let v = c![{ let t = x * 2; t * t + t }, for x in 0..4];
+//! assert_eq!(v, vec![0, 6, 20, 42]);
+//! # }
+//! ```
+//!
+//! `match` expressions work as they are, since the commas between their arms are
+//! inside braces.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let v = c![match x { 0 => "zero", 1 => "one", _ => "many" }, for x in 0..4];
+//! assert_eq!(v, vec!["zero", "one", "many", "many"]);
+//! # }
+//! ```
+//!
+//! `unsafe` blocks are expressions too.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let data = [3, 1, 4];
+//! let ptr = data.as_ptr();
+//! let v = c![unsafe { *ptr.add(i) }, for i in 0..data.len()];
+//! assert_eq!(v, vec![3, 1, 4]);
+//! # }
+//! ```
+//!
+//! An `else` clause after the `if` clauses keeps the elements that fail them, with a
+//! value of their own, like an `if`-`else` expression would.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let v = c![x * 10, for x in 0..5, if x % 2 == 0, else -x];
+//! assert_eq!(v, vec![0, -1, 20, -3, 40]);
+//!
+//! // the same thing, with an `if`-`else` expression
+//! let v = c![if x % 2 == 0 { x * 10 } else { -x }, for x in 0..5];
+//! assert_eq!(v, vec![0, -1, 20, -3, 40]);
+//! # }
+//! ```
+//!
+//! Closures
+//!
+//! Every closure has its own type, so closures that capture the loop variable are boxed
+//! to be collected together.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let fs = c![Box::new(move || x * x) as Box<dyn Fn() -> i32>, for x in 0..4];
+//! assert_eq!(c![f(), for f in fs.iter()], vec![0, 1, 4, 9]);
+//! # }
+//! ```
+//!
+//! Variables of the surrounding scope
+//!
+//! The macros' own bindings never clash with variables of the calling code, whatever
+//! their names. Loop variables, however, shadow outer variables of the same name inside
+//! the comprehension, exactly as in a `for` loop.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let r = vec![10];
+//! let x = 100;
+//! let v = c![r[0] + x, for x in 0..3];
+//! assert_eq!(v, vec![10, 11, 12]);
+//! assert_eq!(x, 100);
+//! # }
+//! ```
+//!
 //! Hashmap Comprehensions
 //!
 //! ```rust
 //! # #[macro_use(c)] extern crate cute;
-//! # use std::collections::HashMap;
+//! # use cute::HashMap;
 //!
 //! # fn main() {
 //! let v = vec!["one", "two", "three"];
@@ -144,7 +552,7 @@
 //!
 //! ```rust
 //! # #[macro_use(c)] extern crate cute;
-//! # use std::collections::HashMap;
+//! # use cute::HashMap;
 //!
 //! # fn main() {
 //! let v: Vec<(&str, i32)> = vec![("one", 1), ("two", 2), ("three", 3)];
@@ -161,7 +569,7 @@
 //!
 //! ```rust
 //! # #[macro_use(c)] extern crate cute;
-//! # use std::collections::HashMap;
+//! # use cute::HashMap;
 //!
 //! # fn main() {
 //! // conditional hashmap comprehension
@@ -178,7 +586,7 @@
 //!
 //! ```rust
 //! # #[macro_use(c)] extern crate cute;
-//! # use std::collections::HashMap;
+//! # use cute::HashMap;
 //!
 //! # fn main() {
 //! // conditional hashmap comprehension from an Iterator
@@ -189,295 +597,5098 @@
 //! assert_eq!(map, e);
 //! # }
 //! ```
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//! # use cute::HashMap;
+//!
+//! # fn main() {
+//! // hashmap comprehension from two parallel iterators
+//! let names = vec!["one", "two", "three"];
+//! let map = c!{name => n, for name in names, zip n in 1..};
+//!
+//! let mut expected: HashMap<&str, i32> = HashMap::new();
+//! expected.insert("one", 1);
+//! expected.insert("two", 2);
+//! expected.insert("three", 3);
+//!
+//! assert_eq!(map, expected);
+//! # }
+//! ```
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//! # use cute::HashMap;
+//!
+//! # fn main() {
+//! // nested hashmap comprehension, the first clause is the inner loop as in the list form
+//! let grid = vec![vec![1, 2], vec![3, 4]];
+//! let cells = c!{(row, col) => v, for (col, &v) in r.iter().enumerate(), for (row, r) in grid.iter().enumerate()};
+//!
+//! let mut expected: HashMap<(usize, usize), i32> = HashMap::new();
+//! expected.insert((0, 0), 1);
+//! expected.insert((0, 1), 2);
+//! expected.insert((1, 0), 3);
+//! expected.insert((1, 1), 4);
+//!
+//! assert_eq!(cells, expected);
+//! # }
+//! ```
+//!
+//! A trailing `counting` keyword, with `count` as the value, counts how many times
+//! each key occurs instead, into a `HashMap<K, usize>`.
+//!
+//! ```rust
+//! # #[macro_use(c)] extern crate cute;
+//!
+//! # fn main() {
+//! let counts = c!{ch => count, for ch in "banana".chars(), counting};
+//! assert_eq!(counts[&'a'], 3);
+//! assert_eq!(counts[&'n'], 2);
+//! # }
+//! ```
+//!
+//! # Without `std`
+//!
+//! With the `use_alloc` feature the crate is `no_std`, and the macros build their
+//! results from `alloc` collections, with `hashbrown` providing `HashMap` and
+//! `HashSet`. The user crate needs `extern crate alloc;`.
+//!
+//! The `hashbrown` feature alone keeps `std`, but makes the `HashMap` and `HashSet`
+//! producing macros use `hashbrown`'s faster, non DoS-resistant default hasher.
+//!
+//! Either feature changes these types for every crate in the build, so code that
+//! names them should use the `cute::HashMap` and `cute::HashSet` re-exports, which
+//! always match what the macros return.
+//!
+//! # Procedural macro
+//!
+//! The `proc` feature re-exports the `cute-proc` crate's `c!` as `cute::proc_c!`. It
+//! accepts the list and map forms with `for` and `if` clauses, and reports syntax
+//! errors on the exact token at fault. It always builds a `std` `Vec` or
+//! `std::collections::HashMap`, so its map form returns a different type from `c!`
+//! when the `hashbrown` or `use_alloc` feature is enabled.
+
+#[cfg(feature = "proc")]
+pub use cute_proc::c as proc_c;
+
+#[cfg(feature = "rayon")]
+#[doc(hidden)]
+pub use rayon;
+
+#[cfg(feature = "futures")]
+#[doc(hidden)]
+pub use futures;
+
+#[cfg(feature = "indexmap")]
+#[doc(hidden)]
+pub use indexmap;
+
+#[cfg(feature = "use_alloc")]
+extern crate alloc;
+
+#[cfg(not(feature = "hashbrown"))]
+use std::collections as hash_collections;
+#[cfg(feature = "hashbrown")]
+use hashbrown as hash_collections;
+
+/// The map type returned by the `HashMap` producing macros, such as the map form
+/// of `c!`, `group_c!` and `freq_c!`.
+///
+/// This is `std::collections::HashMap`, or `hashbrown::HashMap` when any crate in
+/// the build enables the `hashbrown` or `use_alloc` feature. Name the result as
+/// `cute::HashMap` to keep compiling whichever features are enabled.
+pub use hash_collections::HashMap;
+
+/// The set type returned by `s!`, `std::collections::HashSet` or `hashbrown::HashSet`
+/// following the same features as [`HashMap`].
+pub use hash_collections::HashSet;
+
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(not(feature = "use_alloc"))]
+    pub use std::collections::{BTreeMap, BTreeSet, BinaryHeap, LinkedList, VecDeque};
+    #[cfg(not(feature = "use_alloc"))]
+    pub use std::string::String;
+    #[cfg(not(feature = "use_alloc"))]
+    pub use std::vec::Vec;
+    pub use crate::{HashMap, HashSet};
+
+    #[cfg(feature = "use_alloc")]
+    pub use alloc::collections::{BTreeMap, BTreeSet, BinaryHeap, LinkedList, VecDeque};
+    #[cfg(feature = "use_alloc")]
+    pub use alloc::string::String;
+    #[cfg(feature = "use_alloc")]
+    pub use alloc::vec::Vec;
+
+    /// Arrays, and references to arrays, whose length is known at compile time.
+    pub trait ArrayLen {
+        const LEN: usize;
+    }
+
+    impl<T, const N: usize> ArrayLen for [T; N] {
+        const LEN: usize = N;
+    }
+
+    impl<T, const N: usize> ArrayLen for &[T; N] {
+        const LEN: usize = N;
+    }
+
+    impl<T, const N: usize> ArrayLen for &mut [T; N] {
+        const LEN: usize = N;
+    }
+
+    pub struct NonEmpty<A>(::core::marker::PhantomData<A>);
+
+    impl<A: ArrayLen> NonEmpty<A> {
+        pub const OK: () = assert!(A::LEN > 0, "comprehension source is empty");
+    }
+
+    /// Returns `source` unchanged, failing to compile when it is an empty array.
+    pub fn non_empty<A: ArrayLen>(source: A) -> A {
+        #[allow(clippy::let_unit_value)]
+        let () = NonEmpty::<A>::OK;
+        source
+    }
+
+    /// Sums `iter` into its own item type, so `sum_c!` needs no annotation.
+    pub fn sum<T: ::core::iter::Sum<T>, I: Iterator<Item = T>>(iter: I) -> T {
+        iter.sum()
+    }
+
+    /// Multiplies `iter` into its own item type, so `product_c!` needs no annotation.
+    pub fn product<T: ::core::iter::Product<T>, I: Iterator<Item = T>>(iter: I) -> T {
+        iter.product()
+    }
+}
+
+/// Collects `iter` and sorts it, for iterating over its elements in order.
+///
+/// ```rust
+/// # #[macro_use(c)] extern crate cute;
+/// use cute::sorted;
+///
+/// # fn main() {
+/// let v = c![x * 10, for x in sorted(vec![3, 1, 2]), if x > 1];
+/// assert_eq!(v, vec![20, 30]);
+/// # }
+/// ```
+pub fn sorted<I>(iter: I) -> <__private::Vec<I::Item> as IntoIterator>::IntoIter
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    let mut v: __private::Vec<_> = iter.into_iter().collect();
+    v.sort();
+    v.into_iter()
+}
+
+/// Collects `iter` and sorts it with the comparator `compare`, for iterating over
+/// its elements in that order.
+///
+/// ```rust
+/// # #[macro_use(c)] extern crate cute;
+/// use cute::sorted_by;
+///
+/// # fn main() {
+/// let v = c![w, for w in sorted_by(vec!["bb", "a", "ccc"], |a, b| b.len().cmp(&a.len()))];
+/// assert_eq!(v, vec!["ccc", "bb", "a"]);
+/// # }
+/// ```
+pub fn sorted_by<I, F>(iter: I, compare: F) -> <__private::Vec<I::Item> as IntoIterator>::IntoIter
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item, &I::Item) -> ::core::cmp::Ordering,
+{
+    let mut v: __private::Vec<_> = iter.into_iter().collect();
+    v.sort_by(compare);
+    v.into_iter()
+}
+
+#[macro_export]
+macro_rules! c {
 
-#[macro_export]
-macro_rules! c {
-
-     ($e:expr $(, for $i:ident in $iter:expr)+) => {{
-        let mut r = vec![];
-        c!(@loop r.push($e) $(, for $i in $iter)*);
-        r
-    }};
-
-    (@loop $e:expr, for $i:ident in $iter:expr $(, for $j:ident in $rest:expr)*) => {
-        c!(@loop for $i in $iter { $e } $(, for $j in $rest)*);
-    };
-
-   (@loop $e:expr) => {
-        $e
-    };
-
+    (? $($t:tt)+) => (
+        $crate::opt_c![$($t)+]
+    );
+
+    (for $($rest:tt)*) => (
+        compile_error!("missing expression in comprehension, expected `c![expression, for pattern in iterator]`")
+    );
 
-    ($exp:expr, for $i:ident in $iter:expr, if $cond:expr) => (
+    ($exp:expr, for $p:pat in chain($first:expr $(, $more:expr)+) $(, if $cond:expr)*) => (
         {
-            let mut r = vec![];
-            for $i in $iter {
-                if $cond {
-                    r.push($exp.clone());
+            let mut r = $crate::__private::Vec::new();
+            for $p in ::core::iter::IntoIterator::into_iter($first)$(.chain($more))+ {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr, for $p2:pat in $iter2:expr, for $p3:pat in $iter3:expr $(, for $q:pat in $rest:expr)* $(, if $cond:expr)*) => {{
+        let mut r = $crate::__private::Vec::new();
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                r.push($exp);
+            }
+        }, for $p in $iter, for $p2 in $iter2, for $p3 in $iter3 $(, for $q in $rest)*);
+        r
+    }};
+
+     ($e:expr $(, for $p:pat in $iter:expr)+) => {{
+        let mut r = $crate::__private::Vec::new();
+        $crate::c!(@loop r.push($e) $(, for $p in $iter)*);
+        r
+    }};
+
+    (@loop $e:expr, for $p:pat in $iter:expr $(, for $q:pat in $rest:expr)*) => {
+        $crate::c!(@loop for $p in $iter { $e } $(, for $q in $rest)*);
+    };
+
+   (@loop $e:expr) => {
+        $e
+    };
+
+
+    ($exp:expr, while let $p:pat = $source:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            while let $p = $source {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr, while $w:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if !$w {
+                    break;
+                }
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr, skip_while $w:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            let mut skipping = true;
+            for $p in $iter {
+                if skipping && $w {
+                    continue;
+                }
+                skipping = false;
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr, if let $p2:pat = $e:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if let $p2 = $e {
+                    if true $(&& $cond)* {
+                        r.push($exp);
+                    }
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, let $($rest:tt)+) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    $crate::c!(@body r, $exp, let $($rest)+);
+                }
+            }
+            r
+        }
+    );
+
+    (@body $r:ident, $exp:expr) => (
+        $r.push($exp)
+    );
+
+    (@body $r:ident, $exp:expr, let $p:pat = $e:expr, else continue $($rest:tt)*) => (
+        {
+            let $p = $e else { continue; };
+            $crate::c!(@body $r, $exp $($rest)*);
+        }
+    );
+
+    (@body $r:ident, $exp:expr, let $p:pat = $e:expr $(, $($rest:tt)+)?) => (
+        {
+            let $p = $e;
+            $crate::c!(@body $r, $exp $(, $($rest)+)?);
+        }
+    );
+
+    (@body $r:ident, $exp:expr, if let $p:pat = $e:expr $(, $($rest:tt)+)?) => (
+        if let $p = $e {
+            $crate::c!(@body $r, $exp $(, $($rest)+)?);
+        }
+    );
+
+    (@body $r:ident, $exp:expr, if $cond:expr $(, $($rest:tt)+)?) => (
+        if $cond {
+            $crate::c!(@body $r, $exp $(, $($rest)+)?);
+        }
+    );
+
+    (@body $r:ident, $exp:expr, $($rest:tt)*) => (
+        compile_error!("unsupported clause or combination of clauses in comprehension, the clauses after `for pattern in iterator` are `for`, `if`, `if let`, `let`, `while`, `skip_while`, `zip`, `step` and `else`")
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if $($cond)&&+ {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)+, else $alt:expr) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if $($cond)&&+ {
+                    r.push($exp);
+                } else {
+                    r.push($alt);
+                }
+            }
+            r
+        }
+    );
+
+
+    ($exp:expr, for $p:pat in $iter:expr, for $p2:pat in $iter2:expr $(, if $cond:expr)+) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p2 in $iter2 {
+                for $p in $iter {
+                    if $($cond)&&+ {
+                        r.push($exp);
+                    }
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $outer:expr)+, for $p2:pat in $iter2:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p2 in $iter2 {
+                if $($outer)&&+ {
+                    for $p in $iter {
+                        if true $(&& $cond)* {
+                            r.push($exp);
+                        }
+                    }
+                }
+            }
+            r
+        }
+    );
+
+    ($e:expr $(, for $p:pat in $iter:expr)+; $cap:expr) => {{
+        let mut r = $crate::__private::Vec::with_capacity($cap);
+        $crate::c!(@loop r.push($e) $(, for $p in $iter)*);
+        r
+    }};
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)+; $cap:expr) => (
+        {
+            let mut r = $crate::__private::Vec::with_capacity($cap);
+            for $p in $iter {
+                if $($cond)&&+ {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for ($idx:ident, $p:pat) in enumerate $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for ($idx, $p) in ::core::iter::IntoIterator::into_iter($iter).enumerate() {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr, zip $p2:pat in $iter2:expr, zip $p3:pat in $iter3:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for (($p, $p2), $p3) in ::core::iter::IntoIterator::into_iter($iter).zip($iter2).zip($iter3) {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr, zip $p2:pat in $iter2:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for ($p, $p2) in ::core::iter::IntoIterator::into_iter($iter).zip($iter2) {
+                if true $(&& $cond)* {
+                    r.push($exp);
                 }
             }
             r
         }
     );
 
+    ($key:expr => count, for $p:pat in $iter:expr $(, if $cond:expr)*, counting) => (
+        $crate::freq_c![$key, for $p in $iter $(, if $cond)*]
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map = HashMap::new();
+            for $p in $iter {
+                map.insert($key, $val);
+            }
+            map
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr, if let $p2:pat = $e:expr) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map = HashMap::new();
+            for $p in $iter {
+                if let $p2 = $e {
+                    map.insert($key, $val);
+                }
+            }
+            map
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map = HashMap::new();
+            for $p in $iter {
+                if $($cond)&&+ {
+                    map.insert($key, $val);
+                }
+            }
+            map
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr, zip $p2:pat in $iter2:expr $(, if $cond:expr)*) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map = HashMap::new();
+            for ($p, $p2) in ::core::iter::IntoIterator::into_iter($iter).zip($iter2) {
+                if true $(&& $cond)* {
+                    map.insert($key, $val);
+                }
+            }
+            map
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr $(, for $q:pat in $rest:expr)+ $(, if $cond:expr)*) => {{
+        use $crate::__private::HashMap;
+        let mut map = HashMap::new();
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                map.insert($key, $val);
+            }
+        }, for $p in $iter $(, for $q in $rest)+);
+        map
+    }};
+
+    (@step $exp:expr, $p:pat, [$($src:tt)+] step $s:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in ($($src)+).step_by($s) {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    (@step $exp:expr, $p:pat, [$($src:tt)*] $t:tt $($rest:tt)*) => (
+        $crate::c!(@step $exp, $p, [$($src)* $t] $($rest)*)
+    );
+
+    (@step $exp:expr, $p:pat, [$($src:tt)*]) => (
+        compile_error!("unsupported clause or combination of clauses in comprehension, the clauses after `for pattern in iterator` are `for`, `if`, `if let`, `let`, `while`, `skip_while`, `zip`, `step` and `else`")
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr, if $($rest:tt)+) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                $crate::c!(@body r, $exp, if $($rest)+);
+            }
+            r
+        }
+    );
+
+    ($exp:expr, for $p:pat in $($rest:tt)+) => (
+        $crate::c!(@step $exp, $p, [] $($rest)+)
+    );
+
+    (@diagnose) => (
+        compile_error!("malformed comprehension, expected `c![expression, for pattern in iterator]` or `c!{key => value, for pattern in iterator}`")
+    );
+
+    (@diagnose , for $($rest:tt)*) => (
+        $crate::c!(@diagnose $($rest)*)
+    );
+
+    (@diagnose , $p:tt in $($rest:tt)*) => (
+        compile_error!("missing `for` in comprehension, expected `, for pattern in iterator`")
+    );
+
+    (@diagnose $t:tt for $($rest:tt)*) => (
+        compile_error!("missing `,` before `for` in comprehension")
+    );
+
+    (@diagnose $t:tt $($rest:tt)*) => (
+        $crate::c!(@diagnose $($rest)*)
+    );
+
+    ($($t:tt)*) => (
+        $crate::c!(@diagnose $($t)*)
+    );
+}
+/// HashSet comprehension, with the same syntax as the list form of `c!`.
+///
+/// Duplicate values collapse into a single entry.
+///
+/// ```rust
+/// # #[macro_use(s)] extern crate cute;
+/// # use cute::HashSet;
+///
+/// # fn main() {
+/// let sums = s![x + y, for x in 0..3, for y in 0..3];
+/// let expected: HashSet<i32> = [0, 1, 2, 3, 4].iter().cloned().collect();
+/// assert_eq!(sums, expected);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! s {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+) => {{
+        use $crate::__private::HashSet;
+        let mut set = HashSet::new();
+        $crate::c!(@loop { set.insert($e); } $(, for $p in $iter)*);
+        set
+    }};
+
+    ($exp:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)+) => {{
+        use $crate::__private::HashSet;
+        let mut set = HashSet::new();
+        $crate::c!(@loop {
+            if $($cond)&&+ {
+                set.insert($exp);
+            }
+        } $(, for $p in $iter)*);
+        set
+    }};
+}
+
+/// BTreeMap comprehension, with the same syntax as the map form of `c!`.
+///
+/// The resulting map keeps its keys in ascending order.
+///
+/// ```rust
+/// # #[macro_use(c_btree)] extern crate cute;
+///
+/// # fn main() {
+/// let map = c_btree!{key => key * key, for key in (0..5).rev()};
+/// assert_eq!(map.keys().cloned().collect::<Vec<i32>>(), vec![0, 1, 2, 3, 4]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! c_btree {
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr) => (
+        {
+            use $crate::__private::BTreeMap;
+            let mut map = BTreeMap::new();
+            for $p in $iter {
+                map.insert($key, $val);
+            }
+            map
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr, if $cond:expr) => (
+        {
+            use $crate::__private::BTreeMap;
+            let mut map = BTreeMap::new();
+            for $p in $iter {
+                if $cond {
+                    map.insert($key, $val);
+                }
+            }
+            map
+        }
+    );
+}
+
+/// BTreeSet comprehension, with the same syntax as the list form of `c!`.
+///
+/// The resulting set is deduplicated and iterates in ascending order.
+///
+/// ```rust
+/// # #[macro_use(bs)] extern crate cute;
+///
+/// # fn main() {
+/// let squares = bs![x * x, for x in 0..10, if x % 2 == 0];
+/// assert_eq!(squares.into_iter().collect::<Vec<i32>>(), vec![0, 4, 16, 36, 64]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! bs {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+) => {{
+        use $crate::__private::BTreeSet;
+        let mut set = BTreeSet::new();
+        $crate::c!(@loop { set.insert($e); } $(, for $p in $iter)*);
+        set
+    }};
+
+    ($exp:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)+) => {{
+        use $crate::__private::BTreeSet;
+        let mut set = BTreeSet::new();
+        $crate::c!(@loop {
+            if $($cond)&&+ {
+                set.insert($exp);
+            }
+        } $(, for $p in $iter)*);
+        set
+    }};
+}
+
+/// VecDeque comprehension, with the same syntax as the list form of `c!`.
+///
+/// ```rust
+/// # #[macro_use(dq)] extern crate cute;
+///
+/// # fn main() {
+/// let mut window = dq![x * x, for x in 0..5];
+/// assert_eq!(window.pop_front(), Some(0));
+/// assert_eq!(window.pop_back(), Some(16));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! dq {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+) => {{
+        use $crate::__private::VecDeque;
+        let mut r = VecDeque::new();
+        $crate::c!(@loop r.push_back($e) $(, for $p in $iter)*);
+        r
+    }};
+
+    ($exp:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)+) => {{
+        use $crate::__private::VecDeque;
+        let mut r = VecDeque::new();
+        $crate::c!(@loop {
+            if $($cond)&&+ {
+                r.push_back($exp);
+            }
+        } $(, for $p in $iter)*);
+        r
+    }};
+}
+
+/// LinkedList comprehension, with the same syntax as the list form of `c!`.
+///
+/// Elements are appended with `push_back`, in iteration order.
+///
+/// ```rust
+/// # #[macro_use(ll)] extern crate cute;
+///
+/// # fn main() {
+/// let list = ll![x * 2, for x in 0..4, if x != 2];
+/// assert_eq!(list.into_iter().collect::<Vec<i32>>(), vec![0, 2, 6]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ll {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+) => {{
+        use $crate::__private::LinkedList;
+        let mut r = LinkedList::new();
+        $crate::c!(@loop r.push_back($e) $(, for $p in $iter)*);
+        r
+    }};
+
+    ($exp:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)+) => {{
+        use $crate::__private::LinkedList;
+        let mut r = LinkedList::new();
+        $crate::c!(@loop {
+            if $($cond)&&+ {
+                r.push_back($exp);
+            }
+        } $(, for $p in $iter)*);
+        r
+    }};
+}
+
+/// Fixed-size array comprehension, producing a `[T; N]` where `N` follows a `;`.
+///
+/// Panics if the comprehension does not produce exactly `N` elements.
+///
+/// ```rust
+/// # #[macro_use(c_arr)] extern crate cute;
+///
+/// # fn main() {
+/// let squares: [i32; 4] = c_arr![x * x, for x in 0..4; 4];
+/// assert_eq!(squares, [0, 1, 4, 9]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! c_arr {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*; $n:expr) => {{
+        let mut r = $crate::__private::Vec::with_capacity($n);
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                r.push($e);
+            }
+        } $(, for $p in $iter)*);
+        let len = r.len();
+        let arr: [_; $n] = match ::core::convert::TryInto::try_into(r) {
+            Ok(arr) => arr,
+            Err(_) => panic!("expected {} elements, the comprehension produced {}", $n, len),
+        };
+        arr
+    }};
+}
+
+/// Counted comprehension, returning the `Vec` together with the number of elements
+/// it holds.
+///
+/// ```rust
+/// # #[macro_use(c_counted)] extern crate cute;
+///
+/// # fn main() {
+/// let (evens, count) = c_counted![x, for x in 0..10, if x % 2 == 0];
+/// assert_eq!(evens, vec![0, 2, 4, 6, 8]);
+/// assert_eq!(count, 5);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! c_counted {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*) => {{
+        let mut r = $crate::__private::Vec::new();
+        let mut count: usize = 0;
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                r.push($e);
+                count += 1;
+            }
+        } $(, for $p in $iter)*);
+        (r, count)
+    }};
+}
+
+/// Indexed comprehension, pairing each result with its 0-based index into a
+/// `Vec<(usize, T)>`.
+///
+/// The index is the element's position in the source iterator, so elements
+/// rejected by an `if` clause leave gaps. With a trailing `renumber` keyword, it is
+/// the position in the output instead.
+///
+/// ```rust
+/// # #[macro_use(indexed_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = indexed_c![x * 10, for x in 5..9, if x % 2 == 0];
+/// assert_eq!(v, vec![(1, 60), (3, 80)]);
+///
+/// let v = indexed_c![x * 10, for x in 5..9, if x % 2 == 0, renumber];
+/// assert_eq!(v, vec![(0, 60), (1, 80)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! indexed_c {
+
+    ($e:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, renumber) => {{
+        let mut r = $crate::__private::Vec::new();
+        for $p in $iter {
+            if true $(&& $cond)* {
+                let i = r.len();
+                r.push((i, $e));
+            }
+        }
+        r
+    }};
+
+    ($e:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => {{
+        let mut r = $crate::__private::Vec::new();
+        for (i, $p) in ::core::iter::IntoIterator::into_iter($iter).enumerate() {
+            if true $(&& $cond)* {
+                r.push((i, $e));
+            }
+        }
+        r
+    }};
+}
+
+/// Bounded comprehension, collecting at most `N` results, where `N` follows a
+/// trailing `limit` keyword.
+///
+/// The loop stops as soon as the limit is reached, so the source may be unbounded.
+///
+/// ```rust
+/// # #[macro_use(bounded_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = bounded_c![x * x, for x in 1.., if x % 2 == 0, limit 3];
+/// assert_eq!(v, vec![4, 16, 36]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! bounded_c {
+
+    ($e:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, limit $n:expr) => {{
+        let limit: usize = $n;
+        let mut r = $crate::__private::Vec::new();
+        if limit > 0 {
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    r.push($e);
+                    if r.len() == limit {
+                        break;
+                    }
+                }
+            }
+        }
+        r
+    }};
+}
+
+/// Debugging comprehension, producing the same `Vec` as `c!` while printing every
+/// element of the source to stderr with `{:?}` before it is processed.
+///
+/// A trailing `debug` clause replaces the printing with a callback taking a
+/// reference to the element. Either way, the call is only compiled in with
+/// `debug_assertions`, so release builds do no extra work. The default printer
+/// expands to `eprintln!` in the calling crate, so `no_std` callers pass a callback.
+///
+/// ```rust
+/// # #[macro_use(debug_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = debug_c![x * x, for x in 0..4, if x != 2];
+/// assert_eq!(v, vec![0, 1, 9]);
+///
+/// let v = debug_c![x + 1, for x in 0..3, debug |x| eprintln!("processing: {:?}", x)];
+/// assert_eq!(v, vec![1, 2, 3]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! debug_c {
+
+    ($e:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, debug $f:expr) => {{
+        let mut r = $crate::__private::Vec::new();
+        for item in $iter {
+            #[cfg(debug_assertions)]
+            ($f)(&item);
+            let $p = item;
+            if true $(&& $cond)* {
+                r.push($e);
+            }
+        }
+        r
+    }};
+
+    ($e:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        $crate::debug_c![$e, for $p in $iter $(, if $cond)*, debug $crate::__debug_c_printer!()]
+    );
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __debug_c_printer {
+    () => (|item| eprintln!("{:?}", item));
+}
+
+/// Non-empty comprehension over an array, or a reference to one, that fails to
+/// compile when the array is empty.
+///
+/// The length check happens when the code is built, not by `cargo check`.
+///
+/// ```rust
+/// # #[macro_use(const_c)] extern crate cute;
+///
+/// # fn main() {
+/// const PRIMES: [u32; 4] = [2, 3, 5, 7];
+/// let squares = const_c![p * p, for p in PRIMES, if p > 2];
+/// assert_eq!(squares, vec![9, 25, 49]);
+/// # }
+/// ```
+///
+/// ```rust,compile_fail
+/// # #[macro_use(const_c)] extern crate cute;
+///
+/// # fn main() {
+/// let empty: [i32; 0] = [];
+/// let v = const_c![x, for x in empty];
+/// # }
+/// ```
+#[macro_export]
+macro_rules! const_c {
+
+    ($e:expr, for $p:pat in $source:expr $(, if $cond:expr)*) => (
+        $crate::c![$e, for $p in $crate::__private::non_empty($source) $(, if $cond)*]
+    );
+}
+
+/// Lazy comprehension, evaluating to an iterator instead of a `Vec`.
+///
+/// Nothing is computed until the iterator is consumed, so the result can be
+/// chained with other adapters such as `take` or `sum` without allocating.
+/// Like any iterator it can only be consumed once. Several `for` clauses nest as
+/// in `c!`, the first being the innermost loop, and expand to chained `flat_map`s
+/// whose inner closures take their captures by value, so non-`Copy` values of the
+/// surrounding scope should be used through a reference.
+///
+/// ```rust
+/// # #[macro_use(ci)] extern crate cute;
+///
+/// # fn main() {
+/// let total: u64 = ci![x * x, for x in 0..1_000_000u64, if x % 2 == 0].take(3).sum();
+/// assert_eq!(total, 20);
+///
+/// let pairs: Vec<(u32, u32)> = ci![(x, y), for x in 0..y, for y in 1..4].collect();
+/// assert_eq!(pairs, vec![(0, 1), (0, 2), (1, 2), (0, 3), (1, 3), (2, 3)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ci {
+
+    ($exp:expr, for $p:pat in $iter:expr) => (
+        ::core::iter::IntoIterator::into_iter($iter).map(|$p| $exp)
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        ::core::iter::IntoIterator::into_iter($iter).filter_map(|$p| {
+            if $($cond)&&+ {
+                Some($exp)
+            } else {
+                None
+            }
+        })
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, for $q:pat in $rest:expr)+ $(, if $cond:expr)*) => (
+        $crate::ci!(@nest
+            ::core::iter::IntoIterator::into_iter($iter).filter_map(move |$p| {
+                if true $(&& $cond)* {
+                    Some($exp)
+                } else {
+                    None
+                }
+            })
+            $(, for $q in $rest)+
+        )
+    );
+
+    (@nest $inner:expr, for $p:pat in $iter:expr) => (
+        ::core::iter::IntoIterator::into_iter($iter).flat_map(|$p| $inner)
+    );
+
+    (@nest $inner:expr, for $p:pat in $iter:expr $(, for $q:pat in $rest:expr)+) => (
+        $crate::ci!(@nest
+            ::core::iter::IntoIterator::into_iter($iter).flat_map(move |$p| $inner)
+            $(, for $q in $rest)+
+        )
+    );
+}
+
+/// Parallel comprehension, backed by rayon's parallel iterators.
+///
+/// Requires the `rayon` feature. Supports the list and map forms of `c!`
+/// with a single `for` clause; output order matches the sequential version.
+///
+/// ```rust
+/// # #[macro_use(par_c)] extern crate cute;
+/// # use cute::HashMap;
+///
+/// # fn main() {
+/// let squares = par_c![x * x, for x in 0..1000u64, if x % 2 == 0];
+/// assert_eq!(squares[..3], [0, 4, 16]);
+///
+/// let map: HashMap<u64, u64> = par_c!{x => x * x, for x in 0..10u64};
+/// assert_eq!(map[&3], 9);
+/// # }
+/// ```
+#[cfg(feature = "rayon")]
+#[macro_export]
+macro_rules! par_c {
+
+    ($exp:expr, for $p:pat in $iter:expr) => (
+        {
+            use $crate::rayon::prelude::*;
+            $iter.into_par_iter().map(|$p| $exp).collect::<$crate::__private::Vec<_>>()
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        {
+            use $crate::rayon::prelude::*;
+            $iter
+                .into_par_iter()
+                .filter_map(|$p| if $($cond)&&+ { Some($exp) } else { None })
+                .collect::<$crate::__private::Vec<_>>()
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr) => (
+        {
+            use $crate::__private::HashMap;
+            use $crate::rayon::prelude::*;
+            $iter.into_par_iter().map(|$p| ($key, $val)).collect::<HashMap<_, _>>()
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        {
+            use $crate::__private::HashMap;
+            use $crate::rayon::prelude::*;
+            $iter
+                .into_par_iter()
+                .filter_map(|$p| if $($cond)&&+ { Some(($key, $val)) } else { None })
+                .collect::<HashMap<_, _>>()
+        }
+    );
+}
+
+/// Parallel hashmap comprehension, computing the entries on rayon's thread pool
+/// and collecting them with rayon's `FromParallelIterator` impl for `HashMap`.
+///
+/// Requires the `rayon` feature. Worth it when computing the values is expensive.
+///
+/// ```rust
+/// # #[macro_use(parallel_map_c)] extern crate cute;
+///
+/// # fn main() {
+/// let cubes = parallel_map_c!{x => x * x * x, for x in 0..100u64, if x % 10 == 0};
+/// assert_eq!(cubes.len(), 10);
+/// assert_eq!(cubes[&20], 8000);
+/// # }
+/// ```
+#[cfg(feature = "rayon")]
+#[macro_export]
+macro_rules! parallel_map_c {
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            use $crate::__private::HashMap;
+            use $crate::rayon::prelude::*;
+            $iter
+                .into_par_iter()
+                .filter_map(|$p| if true $(&& $cond)* { Some(($key, $val)) } else { None })
+                .collect::<HashMap<_, _>>()
+        }
+    );
+}
+
+/// Fallible comprehension, collecting `Result<T, E>` values into a `Result<Vec<T>, E>`.
+///
+/// Iteration stops at the first `Err`, which is returned as is.
+///
+/// ```rust
+/// # #[macro_use(try_c)] extern crate cute;
+///
+/// # fn main() {
+/// let parsed = try_c![s.parse::<i32>(), for s in vec!["1", "2", "3"]];
+/// assert_eq!(parsed, Ok(vec![1, 2, 3]));
+///
+/// let parsed = try_c![s.parse::<i32>(), for s in vec!["1", "two", "3"]];
+/// assert!(parsed.is_err());
+/// # }
+/// ```
+///
+/// With a leading `try`, the expression can use the `?` operator instead: it is
+/// evaluated inside a closure, the values are collected as they are and the first
+/// error raised by `?` becomes the `Err` of the comprehension.
+///
+/// ```rust
+/// # #[macro_use(try_c)] extern crate cute;
+/// use std::num::ParseIntError;
+///
+/// fn doubled(lines: &[&str]) -> Result<Vec<i32>, ParseIntError> {
+///     try_c![try l.parse::<i32>()? * 2, for l in lines]
+/// }
+///
+/// # fn main() {
+/// assert_eq!(doubled(&["1", "2"]), Ok(vec![2, 4]));
+/// assert!(doubled(&["1", "x"]).is_err());
+/// # }
+/// ```
+///
+/// The iterator is evaluated inside the closure too, so it can use `?` as well,
+/// as when listing a directory.
+///
+/// ```rust
+/// # #[macro_use(try_c)] extern crate cute;
+/// use std::{ffi::OsString, fs, io, path::Path};
+///
+/// fn file_names(dir: &Path) -> io::Result<Vec<OsString>> {
+///     try_c![try entry?.file_name(), for entry in fs::read_dir(dir)?]
+/// }
+///
+/// # fn main() {
+/// assert!(file_names(Path::new("/no/such/directory")).is_err());
+/// # }
+/// ```
+///
+/// The `key => value` form collects into a `Result<HashMap<K, V>, E>` from an
+/// iterator of `Result`s, matching the pattern against each `Ok` value and stopping
+/// at the first `Err`. With a leading `try`, it evaluates inside a closure as above
+/// instead, over an iterator of plain values.
+///
+/// ```rust
+/// # #[macro_use(try_c)] extern crate cute;
+/// # use cute::HashMap;
+///
+/// # fn main() {
+/// let pairs: Vec<Result<(&str, i32), &str>> = vec![Ok(("a", 1)), Ok(("b", 2))];
+/// let map = try_c!{k => v * 10, for (k, v) in pairs};
+/// assert_eq!(map.unwrap()["b"], 20);
+///
+/// let lengths: Result<HashMap<&str, i32>, std::num::ParseIntError> =
+///     try_c!{try s => s.parse::<i32>()?, for s in vec!["1", "x"]};
+/// assert!(lengths.is_err());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! try_c {
+
+    (try $key:expr => $val:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        (|| {
+            let mut r = $crate::__private::HashMap::new();
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    r.insert($key, $val);
+                }
+            }
+            Ok(r)
+        })()
+    );
+
+    (try $exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        (|| {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            Ok(r)
+        })()
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::HashMap::new();
+            let mut err = None;
+            for item in $iter {
+                match item {
+                    Ok($p) => {
+                        if true $(&& $cond)* {
+                            r.insert($key, $val);
+                        }
+                    }
+                    Err(e) => {
+                        err = Some(e);
+                        break;
+                    }
+                }
+            }
+            match err {
+                Some(e) => Err(e),
+                None => Ok(r),
+            }
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            let mut err = None;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    match $exp {
+                        Ok(v) => r.push(v),
+                        Err(e) => {
+                            err = Some(e);
+                            break;
+                        }
+                    }
+                }
+            }
+            match err {
+                Some(e) => Err(e),
+                None => Ok(r),
+            }
+        }
+    );
+}
+
+/// Option-filtering comprehension, keeping the unwrapped `Some` values of an
+/// `Option<T>` expression and discarding the `None`s.
+///
+/// ```rust
+/// # #[macro_use(opt_c)] extern crate cute;
+///
+/// # fn main() {
+/// let parsed: Vec<i32> = opt_c![s.parse().ok(), for s in vec!["1", "two", "3"]];
+/// assert_eq!(parsed, vec![1, 3]);
+///
+/// let quotients = opt_c![x.checked_div(y), for x in [6i32, 8], for y in 0..3];
+/// assert_eq!(quotients, vec![6, 8, 3, 4]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! opt_c {
+
+    ($exp:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*) => {{
+        let mut r = $crate::__private::Vec::new();
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                if let Some(v) = $exp {
+                    r.push(v);
+                }
+            }
+        } $(, for $p in $iter)*);
+        r
+    }};
+}
+
+/// Group-by comprehension, collecting every value that shares a key into a
+/// `HashMap<K, Vec<V>>`.
+///
+/// Values within a group keep their iteration order.
+///
+/// ```rust
+/// # #[macro_use(group_c)] extern crate cute;
+///
+/// # fn main() {
+/// let groups = group_c!{x % 3 => x, for x in 0..10};
+/// assert_eq!(groups[&0], vec![0, 3, 6, 9]);
+/// assert_eq!(groups[&2], vec![2, 5, 8]);
+///
+/// let odd = group_c!{x % 3 => x, for x in 0..10, if x % 2 == 1};
+/// assert_eq!(odd[&0], vec![3, 9]);
+/// assert!(!odd.contains_key(&4));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! group_c {
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map: HashMap<_, $crate::__private::Vec<_>> = HashMap::new();
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    map.entry($key).or_default().push($val);
+                }
+            }
+            map
+        }
+    );
+}
+
+/// Group-by comprehension, an alias that forwards its arguments unchanged to
+/// `group_c!` and accepts the same clauses.
+///
+/// ```rust
+/// # #[macro_use(c_group)] extern crate cute;
+///
+/// # fn main() {
+/// let by_parity = c_group!{x % 2 == 0 => x, for x in 1..7};
+/// assert_eq!(by_parity[&true], vec![2, 4, 6]);
+/// assert_eq!(by_parity[&false], vec![1, 3, 5]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! c_group {
+
+    ($($t:tt)+) => (
+        $crate::group_c!($($t)+)
+    );
+}
+
+/// Inverted map comprehension, swapping the key and value of every pair into a
+/// `HashMap<V, K>`.
+///
+/// When several keys share a value, the last one iterated wins.
+///
+/// ```rust
+/// # #[macro_use(invert_c)] extern crate cute;
+/// # use std::collections::HashMap;
+///
+/// # fn main() {
+/// let codes: HashMap<&str, u8> = [("a", 1), ("b", 2)].iter().cloned().collect();
+/// let names = invert_c!{for (name, code) in codes};
+/// assert_eq!(names[&1], "a");
+/// assert_eq!(names[&2], "b");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! invert_c {
+
+    (for ($k:ident, $v:ident) in $iter:expr $(, if $cond:expr)*) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map = HashMap::new();
+            for ($k, $v) in $iter {
+                if true $(&& $cond)* {
+                    map.insert($v, $k);
+                }
+            }
+            map
+        }
+    );
+}
+
+/// Inverted group-by comprehension, collecting every key that shares a value
+/// into a `HashMap<V, Vec<K>>`.
+///
+/// ```rust
+/// # #[macro_use(invert_group_c)] extern crate cute;
+///
+/// # fn main() {
+/// let scores = vec![("ann", 3), ("bob", 5), ("cid", 3)];
+/// let by_score = invert_group_c!{for (name, score) in scores};
+/// assert_eq!(by_score[&3], vec!["ann", "cid"]);
+/// assert_eq!(by_score[&5], vec!["bob"]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! invert_group_c {
+
+    (for ($k:ident, $v:ident) in $iter:expr $(, if $cond:expr)*) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map: HashMap<_, $crate::__private::Vec<_>> = HashMap::new();
+            for ($k, $v) in $iter {
+                if true $(&& $cond)* {
+                    map.entry($v).or_default().push($k);
+                }
+            }
+            map
+        }
+    );
+}
+
+/// Frequency comprehension, counting how many times each value of the
+/// expression occurs into a `HashMap<T, usize>`.
+///
+/// ```rust
+/// # #[macro_use(freq_c)] extern crate cute;
+///
+/// # fn main() {
+/// let counts = freq_c![ch, for ch in "hello".chars()];
+/// assert_eq!(counts[&'l'], 2);
+/// assert_eq!(counts[&'o'], 1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! freq_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            use $crate::__private::HashMap;
+            let mut map = HashMap::new();
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    *map.entry($exp).or_insert(0usize) += 1;
+                }
+            }
+            map
+        }
+    );
+}
+
+/// Histogram comprehension, an alias that forwards its arguments unchanged to
+/// `freq_c!` and reads naturally with braces, like the map form of `c!`.
+///
+/// ```rust
+/// # #[macro_use(hist_c)] extern crate cute;
+///
+/// # fn main() {
+/// let text = "the cat saw the dog";
+/// let words = hist_c!{word, for word in text.split_whitespace()};
+/// assert_eq!(words["the"], 2);
+/// assert_eq!(words["dog"], 1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! hist_c {
+
+    ($($t:tt)+) => (
+        $crate::freq_c!($($t)+)
+    );
+}
+
+/// Partition comprehension, splitting the results into the ones for which the
+/// condition holds and the ones for which it does not.
+///
+/// ```rust
+/// # #[macro_use(part_c)] extern crate cute;
+///
+/// # fn main() {
+/// let (even, odd) = part_c![x * x, for x in 0..6, if x % 2 == 0];
+/// assert_eq!(even, vec![0, 4, 16]);
+/// assert_eq!(odd, vec![1, 9, 25]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! part_c {
+
+    ($exp:expr, for $p:pat in $iter:expr, if $cond:expr) => (
+        {
+            let mut left = $crate::__private::Vec::new();
+            let mut right = $crate::__private::Vec::new();
+            for $p in $iter {
+                if $cond {
+                    left.push($exp);
+                } else {
+                    right.push($exp);
+                }
+            }
+            (left, right)
+        }
+    );
+}
+
+/// Result-partitioning comprehension, collecting the `Ok` values and the `Err`
+/// values of a `Result<T, E>` expression into a `(Vec<T>, Vec<E>)`.
+///
+/// ```rust
+/// # #[macro_use(partition_results_c)] extern crate cute;
+///
+/// # fn main() {
+/// let (numbers, errors) = partition_results_c![s.parse::<i32>(), for s in vec!["1", "two", "3"]];
+/// assert_eq!(numbers, vec![1, 3]);
+/// assert_eq!(errors.len(), 1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! partition_results_c {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*) => {{
+        let mut oks = $crate::__private::Vec::new();
+        let mut errs = $crate::__private::Vec::new();
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                match $e {
+                    Ok(v) => oks.push(v),
+                    Err(e) => errs.push(e),
+                }
+            }
+        } $(, for $p in $iter)*);
+        (oks, errs)
+    }};
+}
+
+/// Unzip comprehension, splitting pair results into two parallel `Vec`s.
+///
+/// ```rust
+/// # #[macro_use(unzip_c)] extern crate cute;
+///
+/// # fn main() {
+/// let (xs, squares) = unzip_c![(x, x * x), for x in 0..4];
+/// assert_eq!(xs, vec![0, 1, 2, 3]);
+/// assert_eq!(squares, vec![0, 1, 4, 9]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! unzip_c {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*) => {{
+        let mut left = $crate::__private::Vec::new();
+        let mut right = $crate::__private::Vec::new();
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                let (a, b) = $e;
+                left.push(a);
+                right.push(b);
+            }
+        } $(, for $p in $iter)*);
+        (left, right)
+    }};
+}
+
+/// Join comprehension, concatenating string results into a `String` with a
+/// separator, which follows a `;`.
+///
+/// The results are appended as they are produced, without collecting them first.
+///
+/// ```rust
+/// # #[macro_use(join_c)] extern crate cute;
+///
+/// # fn main() {
+/// let csv = join_c![x.to_string(), for x in 0..5, if x != 2; ","];
+/// assert_eq!(csv, "0,1,3,4");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! join_c {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*; $sep:expr) => {{
+        let sep: &str = $sep;
+        let mut r = $crate::__private::String::new();
+        let mut first = true;
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                if !first {
+                    r.push_str(sep);
+                }
+                first = false;
+                r.push_str(&$e);
+            }
+        } $(, for $p in $iter)*);
+        r
+    }};
+}
+
+/// In-place comprehension, evaluating an expression that mutates each element,
+/// given after `do`, instead of collecting new values.
+///
+/// ```rust
+/// # #[macro_use(mutate_c)] extern crate cute;
+///
+/// # fn main() {
+/// let mut v = vec![1, 2, 3, 4];
+/// mutate_c![for x in v.iter_mut(), if *x % 2 == 0, do *x *= 10];
+/// assert_eq!(v, vec![1, 20, 3, 40]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! mutate_c {
+
+    (for $p:pat in $iter:expr $(, if $cond:expr)*, do $e:expr) => {
+        for $p in $iter {
+            if true $(&& $cond)* {
+                $e;
+            }
+        }
+    };
+}
+
+/// Sum comprehension, adding up the results without collecting them first.
+///
+/// The result has the same type as the expression, which must implement
+/// `std::iter::Sum`. An empty comprehension sums to zero.
+///
+/// ```rust
+/// # #[macro_use(sum_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert_eq!(sum_c![x * x, for x in 0..10], 285);
+/// assert_eq!(sum_c![x, for x in 0..10, if x % 2 == 0], 20);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! sum_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        $crate::__private::sum($crate::ci![$exp, for $p in $iter $(, if $cond)*])
+    );
+}
+
+/// Product comprehension, multiplying the results without collecting them first.
+///
+/// The result has the same type as the expression, which must implement
+/// `std::iter::Product`. An empty comprehension yields one.
+///
+/// ```rust
+/// # #[macro_use(product_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert_eq!(product_c![x, for x in 1..=5], 120);
+/// assert_eq!(product_c![x, for x in 1..=5, if x % 2 == 1], 15);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! product_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        $crate::__private::product($crate::ci![$exp, for $p in $iter $(, if $cond)*])
+    );
+}
+
+/// Counting comprehension, returning how many elements a comprehension would
+/// produce without collecting them.
+///
+/// The expression is optional; when given it is still evaluated for each
+/// counted element, so `count_c![...]` always equals `c![...].len()`.
+///
+/// ```rust
+/// # #[macro_use(count_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert_eq!(count_c![for x in 0..10, if x % 3 == 0], 4);
+/// assert_eq!(count_c![x * x, for x in 0..10, if x * x > 50], 2);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! count_c {
+
+    (for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut count: usize = 0;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    count += 1;
+                }
+            }
+            count
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut count: usize = 0;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    let _ = $exp;
+                    count += 1;
+                }
+            }
+            count
+        }
+    );
+}
+
+/// Sorted comprehension, accepting anything the list form of `c!` does and
+/// returning the results in ascending order.
+///
+/// ```rust
+/// # #[macro_use(sorted_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = sorted_c![x % 5, for x in 0..10, if x % 2 == 1];
+/// assert_eq!(v, vec![0, 1, 2, 3, 4]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! sorted_c {
+
+    ($($t:tt)+) => (
+        {
+            let mut r = $crate::c![$($t)+];
+            r.sort();
+            r
+        }
+    );
+}
+
+/// Sorted comprehension ordered by a key computed from the loop variables.
+///
+/// The sort is stable, so results with equal keys keep their iteration order.
+///
+/// ```rust
+/// # #[macro_use(sorted_by_c)] extern crate cute;
+///
+/// # fn main() {
+/// let words = sorted_by_c![w, for w in vec!["ccc", "a", "bb", "d"], by w.len()];
+/// assert_eq!(words, vec!["a", "d", "bb", "ccc"]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! sorted_by_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, by $key:expr) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    r.push(($key, $exp));
+                }
+            }
+            r.sort_by(|a, b| a.0.cmp(&b.0));
+            r.into_iter().map(|(_, v)| v).collect::<$crate::__private::Vec<_>>()
+        }
+    );
+}
+
+/// Deduplicating comprehension, accepting anything the list form of `c!` does
+/// and dropping results equal to the one right before them.
+///
+/// Only consecutive duplicates are removed, as with `Vec::dedup`.
+///
+/// ```rust
+/// # #[macro_use(dedup_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = dedup_c![x / 3, for x in 0..10];
+/// assert_eq!(v, vec![0, 1, 2, 3]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! dedup_c {
+
+    ($($t:tt)+) => (
+        {
+            let mut r = $crate::c![$($t)+];
+            r.dedup();
+            r
+        }
+    );
+}
+
+/// Unique comprehension, keeping only the first occurrence of every distinct
+/// result while preserving iteration order.
+///
+/// Unlike `dedup_c!`, duplicates are dropped wherever they appear. Results must
+/// implement `Hash`, `Eq` and `Clone`.
+///
+/// ```rust
+/// # #[macro_use(unique_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = unique_c![x % 3, for x in (0..10).rev()];
+/// assert_eq!(v, vec![0, 2, 1]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! unique_c {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*) => {{
+        use $crate::__private::HashSet;
+        let mut seen = HashSet::new();
+        let mut r = $crate::__private::Vec::new();
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                let v = $e;
+                if seen.insert(v.clone()) {
+                    r.push(v);
+                }
+            }
+        } $(, for $p in $iter)*);
+        r
+    }};
+}
+
+/// Deduplicating comprehension that compares a key computed from the loop
+/// variables, keeping the first result of every run of equal keys.
+///
+/// ```rust
+/// # #[macro_use(dedup_by_key_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = dedup_by_key_c![w, for w in vec!["a", "b", "cc", "dd", "e"], by w.len()];
+/// assert_eq!(v, vec!["a", "cc", "e"]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! dedup_by_key_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, by $key:expr) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            let mut last = None;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    let key = Some($key);
+                    if key != last {
+                        r.push($exp);
+                        last = key;
+                    }
+                }
+            }
+            r
+        }
+    );
+}
+
+/// Maximum comprehension, returning the largest result or `None` when nothing
+/// is produced.
+///
+/// Values are compared with `PartialOrd`, so floats are supported. A trailing
+/// `by` clause compares on a key instead of the value itself. As with
+/// `Iterator::max`, the last of several equal maxima is returned.
+///
+/// ```rust
+/// # #[macro_use(max_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert_eq!(max_c![x % 7, for x in 0..20], Some(6));
+/// assert_eq!(max_c![s, for s in vec!["a", "ccc", "bb"], by s.len()], Some("ccc"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! max_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, by $key:expr) => (
+        {
+            let mut best = None;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    let key = $key;
+                    best = match best {
+                        None => Some((key, $exp)),
+                        Some((k, v)) => if key >= k { Some((key, $exp)) } else { Some((k, v)) },
+                    };
+                }
+            }
+            best.map(|(_, v)| v)
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut best = None;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    let v = $exp;
+                    best = match best {
+                        None => Some(v),
+                        Some(b) => if v >= b { Some(v) } else { Some(b) },
+                    };
+                }
+            }
+            best
+        }
+    );
+}
+
+/// Minimum comprehension, returning the smallest result or `None` when nothing
+/// is produced.
+///
+/// Values are compared with `PartialOrd`, so floats are supported. A trailing
+/// `by` clause compares on a key instead of the value itself. As with
+/// `Iterator::min`, the first of several equal minima is returned.
+///
+/// ```rust
+/// # #[macro_use(min_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert_eq!(min_c![x * x, for x in -3..3, if x != 0], Some(1));
+/// assert_eq!(min_c![s, for s in vec!["ccc", "a", "bb"], by s.len()], Some("a"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! min_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*, by $key:expr) => (
+        {
+            let mut best = None;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    let key = $key;
+                    best = match best {
+                        None => Some((key, $exp)),
+                        Some((k, v)) => if key < k { Some((key, $exp)) } else { Some((k, v)) },
+                    };
+                }
+            }
+            best.map(|(_, v)| v)
+        }
+    );
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut best = None;
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    let v = $exp;
+                    best = match best {
+                        None => Some(v),
+                        Some(b) => if v < b { Some(v) } else { Some(b) },
+                    };
+                }
+            }
+            best
+        }
+    );
+}
+
+/// Fold comprehension, threading an accumulator through the loop.
+///
+/// `fold_c![init, acc, expr, for x in iter]` starts with `acc = init`, sets
+/// `acc = expr` for every element and evaluates to the final `acc`.
+///
+/// ```rust
+/// # #[macro_use(fold_c)] extern crate cute;
+///
+/// # fn main() {
+/// let total = fold_c![0, acc, acc + x, for x in 1..=4];
+/// assert_eq!(total, 10);
+///
+/// let csv = fold_c![String::new(), s, s + &x.to_string() + ",", for x in 0..3];
+/// assert_eq!(csv, "0,1,2,");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! fold_c {
+
+    ($init:expr, $acc:ident, $exp:expr $(, for $p:pat in $iter:expr)+) => {{
+        let mut $acc = $init;
+        $crate::c!(@loop { $acc = $exp; } $(, for $p in $iter)*);
+        $acc
+    }};
+
+    ($init:expr, $acc:ident, $exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        {
+            let mut $acc = $init;
+            for $p in $iter {
+                if $($cond)&&+ {
+                    $acc = $exp;
+                }
+            }
+            $acc
+        }
+    );
+}
+
+/// Scan comprehension, collecting every intermediate value of an accumulator.
+///
+/// `scan_c![acc, init, expr, for x in iter]` starts with `acc = init`, sets
+/// `acc = expr` for every element and collects a clone of each new `acc`.
+///
+/// ```rust
+/// # #[macro_use(scan_c)] extern crate cute;
+///
+/// # fn main() {
+/// let prefix_sums = scan_c![acc, 0, acc + x, for x in 1..=5];
+/// assert_eq!(prefix_sums, vec![1, 3, 6, 10, 15]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! scan_c {
+
+    ($acc:ident, $init:expr, $exp:expr $(, for $p:pat in $iter:expr)+ $(, if $cond:expr)*) => {{
+        let mut $acc = $init;
+        let mut r = $crate::__private::Vec::new();
+        $crate::c!(@loop {
+            if true $(&& $cond)* {
+                $acc = $exp;
+                r.push($acc.clone());
+            }
+        } $(, for $p in $iter)*);
+        r
+    }};
+}
+
+/// Short-circuiting comprehension, true if the condition holds for any element.
+///
+/// Iteration stops at the first element that satisfies the condition.
+///
+/// ```rust
+/// # #[macro_use(any_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert!(any_c![x * x > 50, for x in 0..10]);
+/// assert!(!any_c![x + y > 10, for x in 0..3, for y in 0..3]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! any_c {
+
+    ($cond:expr $(, for $p:pat in $iter:expr)+) => {
+        'any: {
+            $crate::c!(@loop { if $cond { break 'any true; } } $(, for $p in $iter)*);
+            false
+        }
+    };
+}
+
+/// Short-circuiting comprehension, true if the condition holds for every element.
+///
+/// Iteration stops at the first element that does not satisfy the condition.
+///
+/// ```rust
+/// # #[macro_use(all_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert!(all_c![x < 10, for x in 0..10]);
+/// assert!(!all_c![x != y, for x in 0..3, for y in 0..3]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! all_c {
+
+    ($cond:expr $(, for $p:pat in $iter:expr)+) => {
+        'all: {
+            $crate::c!(@loop { if !$cond { break 'all false; } } $(, for $p in $iter)*);
+            true
+        }
+    };
+}
+
+/// Search comprehension, returning the expression for the first element that
+/// satisfies the conditions, or `None`.
+///
+/// Iteration stops at the first match, like `Iterator::find_map`.
+///
+/// ```rust
+/// # #[macro_use(find_c)] extern crate cute;
+///
+/// # fn main() {
+/// assert_eq!(find_c![x * x, for x in 1.., if x % 7 == 0], Some(49));
+/// assert_eq!(find_c![x, for x in 0..5, if x > 10], None);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! find_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => {
+        'find: {
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    break 'find Some($exp);
+                }
+            }
+            None
+        }
+    };
+}
+
+/// Position comprehension, returning the zero-based index of the first element
+/// that satisfies the conditions, or `None`.
+///
+/// ```rust
+/// # #[macro_use(position_c)] extern crate cute;
+///
+/// # fn main() {
+/// let v = vec![1, 4, 9, 16];
+/// assert_eq!(position_c![for x in v.iter(), if *x > 5], Some(2));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! position_c {
+
+    (for $p:pat in $iter:expr $(, if $cond:expr)+) => {
+        'position: {
+            let mut index: usize = 0;
+            for $p in $iter {
+                if $($cond)&&+ {
+                    break 'position Some(index);
+                }
+                index += 1;
+            }
+            None
+        }
+    };
+}
+
+/// Flattening comprehension over an iterator of iterables, binding each inner
+/// item in turn.
+///
+/// ```rust
+/// # #[macro_use(flatten_c)] extern crate cute;
+///
+/// # fn main() {
+/// let nested = vec![vec![1, 2], vec![], vec![3]];
+/// assert_eq!(flatten_c![x * 10, for x in nested], vec![10, 20, 30]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! flatten_c {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in ::core::iter::IntoIterator::into_iter($iter).flatten() {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+}
+
+/// Chunking comprehension, grouping the elements into `Vec`s of `n` elements.
+///
+/// The last chunk holds the remainder and may be shorter. Panics if `n` is zero.
+///
+/// ```rust
+/// # #[macro_use(chunk_c)] extern crate cute;
+///
+/// # fn main() {
+/// let chunks = chunk_c![2, for x in 0..5];
+/// assert_eq!(chunks, vec![vec![0, 1], vec![2, 3], vec![4]]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! chunk_c {
+
+    ($n:expr, for $i:ident in $iter:expr $(, if $cond:expr)*) => (
+        {
+            let size: usize = $n;
+            assert!(size != 0, "chunk size must be non-zero");
+            let mut chunks = $crate::__private::Vec::new();
+            let mut chunk = $crate::__private::Vec::with_capacity(size);
+            for $i in $iter {
+                if true $(&& $cond)* {
+                    chunk.push($i);
+                    if chunk.len() == size {
+                        chunks.push(::core::mem::replace(&mut chunk, $crate::__private::Vec::with_capacity(size)));
+                    }
+                }
+            }
+            if !chunk.is_empty() {
+                chunks.push(chunk);
+            }
+            chunks
+        }
+    );
+}
+
+/// Pairwise comprehension, over the consecutive pairs of elements of a source.
+///
+/// The elements are collected first, and the pattern is matched against a pair of
+/// references to two neighbouring elements, so `(&a, &b)` copies them out.
+///
+/// ```rust
+/// # #[macro_use(pairwise_c)] extern crate cute;
+///
+/// # fn main() {
+/// let deltas = pairwise_c![b - a, for (a, b) in consecutive_pairs(vec![1, 3, 6, 10])];
+/// assert_eq!(deltas, vec![2, 3, 4]);
+///
+/// let rises = pairwise_c![(a, b), for (&a, &b) in consecutive_pairs([5, 2, 4, 8]), if b > a];
+/// assert_eq!(rises, vec![(2, 4), (4, 8)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! pairwise_c {
+
+    ($exp:expr, for $p:pat in consecutive_pairs($iter:expr) $(, if $cond:expr)*) => (
+        {
+            let items: $crate::__private::Vec<_> = ::core::iter::IntoIterator::into_iter($iter).collect();
+            let mut r = $crate::__private::Vec::new();
+            for w in items.windows(2) {
+                let $p = (&w[0], &w[1]);
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+}
+
+/// Cartesian product comprehension, in row-major order: the first source is the
+/// outermost loop and the last source the innermost.
+///
+/// As with nested `c!` clauses, inner sources are evaluated once per element of
+/// the sources before them.
+///
+/// ```rust
+/// # #[macro_use(cartesian_c)] extern crate cute;
+///
+/// # fn main() {
+/// let pairs = cartesian_c![(x, y), x in 0..2, y in 0..2];
+/// assert_eq!(pairs, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! cartesian_c {
+
+    ($exp:expr, $p:pat in $iter:expr, $p2:pat in $iter2:expr) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                for $p2 in $iter2 {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+
+    ($exp:expr, $p:pat in $iter:expr, $p2:pat in $iter2:expr, $p3:pat in $iter3:expr) => (
+        {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                for $p2 in $iter2 {
+                    for $p3 in $iter3 {
+                        r.push($exp);
+                    }
+                }
+            }
+            r
+        }
+    );
+}
+
+/// Async comprehension, evaluating to a future that runs the loop and resolves
+/// to the collected `Vec`.
+///
+/// The expression may use `.await`; elements are awaited one after another, not
+/// concurrently. Captured variables are moved into the future.
+///
+/// ```rust
+/// # #[macro_use(ac)] extern crate cute;
+///
+/// # fn main() {
+/// async fn double(x: i32) -> i32 {
+///     x * 2
+/// }
+///
+/// let future = ac![double(x).await, for x in 0..3];
+/// assert_eq!(futures::executor::block_on(future), vec![0, 2, 4]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ac {
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)*) => (
+        async move {
+            let mut r = $crate::__private::Vec::new();
+            for $p in $iter {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+}
+
+/// Stream comprehension, evaluating to a future that drains a
+/// `futures::Stream` and resolves to the collected `Vec`.
+///
+/// Requires the `futures` feature.
+///
+/// ```rust
+/// # #[macro_use(stream_c)] extern crate cute;
+///
+/// # fn main() {
+/// let numbers = futures::stream::iter(vec![1, 2, 3, 4]);
+/// let future = stream_c![x * 2, for x in numbers, if x != 3];
+/// assert_eq!(futures::executor::block_on(future), vec![2, 4, 8]);
+/// # }
+/// ```
+#[cfg(feature = "futures")]
+#[macro_export]
+macro_rules! stream_c {
+
+    ($exp:expr, for $p:pat in $stream:expr $(, if $cond:expr)*) => (
+        async move {
+            use $crate::futures::stream::StreamExt;
+            let mut stream = ::core::pin::pin!($stream);
+            let mut r = $crate::__private::Vec::new();
+            while let Some($p) = stream.next().await {
+                if true $(&& $cond)* {
+                    r.push($exp);
+                }
+            }
+            r
+        }
+    );
+}
+
+/// Ordered map comprehension, producing an `IndexMap` that keeps its keys in
+/// insertion order.
+///
+/// Requires the `indexmap` feature.
+///
+/// ```rust
+/// # #[macro_use(oc)] extern crate cute;
+///
+/// # fn main() {
+/// let map = oc!{key => key.len(), for key in vec!["zero", "one", "two"]};
+/// assert_eq!(map.keys().cloned().collect::<Vec<&str>>(), vec!["zero", "one", "two"]);
+/// # }
+/// ```
+#[cfg(feature = "indexmap")]
+#[macro_export]
+macro_rules! oc {
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr) => (
+        {
+            use $crate::indexmap::IndexMap;
+            let mut map = IndexMap::new();
+            for $p in $iter {
+                map.insert($key, $val);
+            }
+            map
+        }
+    );
+
+    ($key:expr => $val:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        {
+            use $crate::indexmap::IndexMap;
+            let mut map = IndexMap::new();
+            for $p in $iter {
+                if $($cond)&&+ {
+                    map.insert($key, $val);
+                }
+            }
+            map
+        }
+    );
+}
+
+/// BinaryHeap comprehension, with the same syntax as the list form of `c!`.
+///
+/// The resulting max-heap pops its elements in descending order.
+///
+/// ```rust
+/// # #[macro_use(heap_c)] extern crate cute;
+///
+/// # fn main() {
+/// let mut heap = heap_c![x * x, for x in -3..3];
+/// assert_eq!(heap.pop(), Some(9));
+/// assert_eq!(heap.pop(), Some(4));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! heap_c {
+
+    ($e:expr $(, for $p:pat in $iter:expr)+) => {{
+        use $crate::__private::BinaryHeap;
+        let mut heap = BinaryHeap::new();
+        $crate::c!(@loop heap.push($e) $(, for $p in $iter)*);
+        heap
+    }};
+
+    ($exp:expr, for $p:pat in $iter:expr $(, if $cond:expr)+) => (
+        {
+            use $crate::__private::BinaryHeap;
+            let mut heap = BinaryHeap::new();
+            for $p in $iter {
+                if $($cond)&&+ {
+                    heap.push($exp);
+                }
+            }
+            heap
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet, LinkedList, VecDeque};
+    use crate::{HashMap, HashSet};
+    #[test]
+    fn simple_comprehension() {
+        let squares: Vec<usize> = c![x*x, for x in 0..10];
+        assert_eq!(squares, vec![0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+    }
+
+    #[test]
+    fn filter_comprehension() {
+        let squares = c![x*x, for x in 0..10, if x % 2 == 0];
+        assert_eq!(squares, vec![0, 4, 16, 36, 64]);
+    }
+
+    #[test]
+    fn simple_nested_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let flat: Vec<usize> = c![x, for x in y, for y in nested];
+        assert_eq!(flat, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn filter_nested_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let even_flat: Vec<usize> = c![x, for x in y, for y in nested, if x % 2 == 0];
+        assert_eq!(even_flat, vec![2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn repeated_nested_comprehension() {
+        let n: i32 = 10;
+        let triples = c![(x,y, z), for z in y..n+1, for y in x..n+1, for x in 1..n+1, if x.pow(2) + y.pow(2) == z.pow(2)];
+        println!("{:?}", triples);
+        assert_eq!(triples, vec![(3, 4, 5), (6, 8, 10)]);
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec)]
+    fn iter_nested_comprehension() {
+        let x = c![(x, y), for x in 0..2u8, for y in vec!['a', 'b']];
+        assert_eq!(x, vec![(0, 'a'), (1, 'a'), (0, 'b'), (1, 'b')]);
+    }
+
+    #[test]
+    fn vector_to_iter_comprehension() {
+        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
+        let output: Vec<i32> = c![x*2, for x in vec.iter()];
+        assert_eq!(output, vec![-8, -4, 0, 4, 8]);
+    }
+
+    #[test]
+    fn filter_comprehension_two() {
+        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
+        let output: Vec<i32> = c![*x, for x in vec.iter(), if *x >= 0i32];
+        assert_eq!(output, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn apply_function_comprehension() {
+        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
+        let output: Vec<i32> = c![x.abs(), for x in vec.iter()];
+        assert_eq!(output, vec![4, 2, 0, 2, 4]);
+    }
+
+    #[test]
+    fn apply_user_defined_function() {
+        fn square(x: i32) -> i32 {
+            x * x
+        }
+
+        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
+        let squares: Vec<i32> = c![square(x), for x in vec];
+        assert_eq!(squares, vec![16, 4, 0, 4, 16]);
+    }
+
+    #[test]
+    fn hashmap_comprehension() {
+        let v = vec!["one", "two", "three"];
+        let map = c!{key => key.to_uppercase(), for key in v};
+        let mut expected: HashMap<&str, String> = HashMap::new();
+        expected.insert("one", String::from("ONE"));
+        expected.insert("two", String::from("TWO"));
+        expected.insert("three", String::from("THREE"));
+
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn hashmap_comprehension_two() {
+        let v = vec!["one", "two", "three"];
+        let map = c!{format!("{}-true", key) => key.to_uppercase(), for key in v};
+        let mut expected: HashMap<String, String> = HashMap::new();
+        expected.insert(String::from("one-true"), String::from("ONE"));
+        expected.insert(String::from("two-true"), String::from("TWO"));
+        expected.insert(String::from("three-true"), String::from("THREE"));
+
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn hashmap_comprehension_three() {
+        let v: Vec<(String, i32)> = vec![
+            (String::from("one"), 1),
+            (String::from("two"), 2),
+            (String::from("three"), 3),
+        ];
+        let map = c!{key => val, for (key, val) in v};
+
+        let mut expected: HashMap<String, i32> = HashMap::new();
+        expected.insert(String::from("one"), 1);
+        expected.insert(String::from("two"), 2);
+        expected.insert(String::from("three"), 3);
+
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn hashmap_tuple_comprehension() {
+        let v: Vec<(&str, i32)> = vec![("one", 1), ("two", 2), ("three", 3)];
+        let map = c!{key => val, for (key, val) in v};
+
+        let mut expected: HashMap<&str, i32> = HashMap::new();
+        expected.insert("one", 1);
+        expected.insert("two", 2);
+        expected.insert("three", 3);
+
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn conditional_hashmap_tuple_comprehension() {
+        let v: Vec<(&str, i32)> = vec![("one", 1), ("two", 2), ("three", 3)];
+        let map = c!{key => val, for (key, val) in v, if val == 1 || val == 2};
+
+        let mut expected: HashMap<&str, i32> = HashMap::new();
+        expected.insert("one", 1);
+        expected.insert("two", 2);
+
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec)]
+    fn hashmap_from_iter() {
+        let map = c!{*key => key*key, for key in vec![1,2].iter()};
+        let mut e: HashMap<i32, i32> = HashMap::new();
+        e.insert(1, 1);
+        e.insert(2, 4);
+
+        assert_eq!(map, e);
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec)]
+    fn conditional_hashmap_from_iter() {
+        let map = c!{*key => key*key, for key in vec![1,2].iter(), if *key % 2 == 0};
+        let mut e: HashMap<i32, i32> = HashMap::new();
+        e.insert(2, 4);
+
+        assert_eq!(map, e);
+    }
+
+    #[test]
+    fn hashmap_from_range() {
+        let map = c!{key => key*key, for key in 1..3};
+        let mut e: HashMap<i32, i32> = HashMap::new();
+        e.insert(1, 1);
+        e.insert(2, 4);
+
+        assert_eq!(map, e);
+    }
+
+    #[test]
+    fn conditional_hashmap_from_range() {
+        let map = c!{key => key*key, for key in 1..6, if key % 2 == 1};
+        let mut e: HashMap<i32, i32> = HashMap::new();
+        e.insert(1, 1);
+        e.insert(3, 9);
+        e.insert(5, 25);
+
+        assert_eq!(map, e);
+    }
+
+    #[test]
+    fn hashset_comprehension() {
+        let set = s![x % 3, for x in 0..10];
+        let expected: HashSet<i32> = [0, 1, 2].iter().cloned().collect();
+
+        assert_eq!(set, expected);
+    }
+
+    #[test]
+    fn conditional_hashset_comprehension() {
+        let set = s![x * x, for x in -3..4, if x != 0];
+        let expected: HashSet<i32> = [1, 4, 9].iter().cloned().collect();
+
+        assert_eq!(set, expected);
+    }
+
+    #[test]
+    fn nested_hashset_comprehension() {
+        let set = s![x + y, for x in 0..5, for y in 0..5];
+        let expected: HashSet<i32> = (0..9).collect();
+
+        assert_eq!(set, expected);
+    }
+
+    #[test]
+    fn conditional_nested_hashset_comprehension() {
+        let set = s![x * y, for x in 0..4, for y in 0..4, if x != y, if x * y > 0];
+        let expected: HashSet<i32> = [2, 3, 6].iter().cloned().collect();
+        assert_eq!(set, expected);
+
+        let set = s![x + y + z, for z in 0..2, for y in 0..2, for x in 0..2, if x == y];
+        assert_eq!(set, s![2 * x + z, for z in 0..2, for x in 0..2]);
+    }
+
+    #[test]
+    fn btreemap_comprehension() {
+        let map = c_btree!{key => key * key, for key in [3, 1, 4, 2].iter().cloned()};
+        let mut expected: BTreeMap<i32, i32> = BTreeMap::new();
+        expected.insert(1, 1);
+        expected.insert(2, 4);
+        expected.insert(3, 9);
+        expected.insert(4, 16);
+
+        assert_eq!(map, expected);
+        assert_eq!(map.keys().cloned().collect::<Vec<i32>>(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn btreemap_tuple_comprehension() {
+        let v: Vec<(&str, i32)> = vec![("two", 2), ("three", 3), ("one", 1)];
+        let map = c_btree!{key => val, for (key, val) in v};
+
+        assert_eq!(map.keys().cloned().collect::<Vec<&str>>(), vec!["one", "three", "two"]);
+    }
+
+    #[test]
+    fn conditional_btreemap_comprehension() {
+        let map = c_btree!{key => key * key, for key in (0..10).rev(), if key % 3 == 0};
+        let keys: Vec<i32> = map.keys().cloned().collect();
+
+        assert_eq!(keys, vec![0, 3, 6, 9]);
+        assert_eq!(map[&6], 36);
+    }
+
+    #[test]
+    fn btreeset_comprehension() {
+        let set = bs![x % 4, for x in (0..10).rev()];
+        let expected: BTreeSet<i32> = [0, 1, 2, 3].iter().cloned().collect();
+
+        assert_eq!(set, expected);
+        assert_eq!(set.into_iter().collect::<Vec<i32>>(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn conditional_btreeset_comprehension() {
+        let set = bs![x * x, for x in (-4..5).rev(), if x % 2 == 0];
+
+        assert_eq!(set.into_iter().collect::<Vec<i32>>(), vec![0, 4, 16]);
+    }
+
+    #[test]
+    fn nested_btreeset_comprehension() {
+        let set = bs![x * y, for x in 1..4, for y in (1..4).rev()];
+
+        assert_eq!(set.into_iter().collect::<Vec<i32>>(), vec![1, 2, 3, 4, 6, 9]);
+    }
+
+    #[test]
+    fn conditional_nested_btreeset_comprehension() {
+        let set = bs![x * y, for x in 1..4, for y in 1..4, if x != y, if x * y % 2 == 0];
+        assert_eq!(set.into_iter().collect::<Vec<i32>>(), vec![2, 6]);
+
+        let set = bs![(x, y, z), for x in 0..2, for y in 0..2, for z in 0..2, if x + y + z == 1];
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), vec![(0, 0, 1), (0, 1, 0), (1, 0, 0)]);
+    }
+
+    #[test]
+    fn vecdeque_comprehension() {
+        let mut deque: VecDeque<i32> = dq![x * x, for x in 0..5];
+        assert_eq!(deque, c![x * x, for x in 0..5]);
+
+        assert_eq!(deque.pop_front(), Some(0));
+        assert_eq!(deque.pop_back(), Some(16));
+    }
+
+    #[test]
+    fn conditional_vecdeque_comprehension() {
+        let deque = dq![x, for x in 0..10, if x % 3 == 0];
+        assert_eq!(deque, VecDeque::from(vec![0, 3, 6, 9]));
+    }
+
+    #[test]
+    fn nested_vecdeque_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let flat: VecDeque<usize> = dq![x, for x in y, for y in nested.clone()];
+        assert_eq!(flat, c![x, for x in y, for y in nested.clone()]);
+
+        let even_flat: VecDeque<usize> = dq![x, for x in y, for y in nested.clone(), if x % 2 == 0];
+        assert_eq!(even_flat, c![x, for x in y, for y in nested, if x % 2 == 0]);
+    }
+
+    #[test]
+    fn repeated_nested_vecdeque_comprehension() {
+        let n: i32 = 10;
+        let triples = dq![(x, y, z), for z in y..n+1, for y in x..n+1, for x in 1..n+1, if x.pow(2) + y.pow(2) == z.pow(2)];
+        assert_eq!(triples, VecDeque::from(vec![(3, 4, 5), (6, 8, 10)]));
+    }
+
+    #[test]
+    fn linkedlist_comprehension() {
+        let list: LinkedList<i32> = ll![x * x, for x in 0..5];
+        assert_eq!(list.into_iter().collect::<Vec<i32>>(), c![x * x, for x in 0..5]);
+    }
+
+    #[test]
+    fn conditional_linkedlist_comprehension() {
+        let list = ll![x, for x in 0..10, if x % 3 == 0];
+        assert_eq!(list.into_iter().collect::<Vec<i32>>(), c![x, for x in 0..10, if x % 3 == 0]);
+    }
+
+    #[test]
+    fn nested_linkedlist_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let flat: LinkedList<usize> = ll![x, for x in y, for y in nested.clone()];
+        assert_eq!(flat.into_iter().collect::<Vec<usize>>(), c![x, for x in y, for y in nested.clone()]);
+
+        let even_flat: LinkedList<usize> = ll![x, for x in y, for y in nested.clone(), if x % 2 == 0];
+        assert_eq!(even_flat.into_iter().collect::<Vec<usize>>(), c![x, for x in y, for y in nested, if x % 2 == 0]);
+    }
+
+    #[test]
+    fn repeated_nested_linkedlist_comprehension() {
+        let n: i32 = 10;
+        let triples = ll![(x, y, z), for z in y..n+1, for y in x..n+1, for x in 1..n+1, if x.pow(2) + y.pow(2) == z.pow(2)];
+        assert_eq!(triples.into_iter().collect::<Vec<(i32, i32, i32)>>(), vec![(3, 4, 5), (6, 8, 10)]);
+    }
+
+    #[test]
+    fn chained_conditions_comprehension() {
+        let v = c![x, for x in 0..10, if x > 2, if x < 8];
+        assert_eq!(v, vec![3, 4, 5, 6, 7]);
+
+        let v = c![x, for x in 0..30, if x % 2 == 0, if x % 3 == 0, if x % 5 == 0];
+        assert_eq!(v, vec![0]);
+    }
+
+    #[test]
+    fn chained_conditions_short_circuit() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let check = |x: i32| {
+            calls.set(calls.get() + 1);
+            x < 8
+        };
+        let v = c![x, for x in 0..10, if x > 2, if check(x)];
+
+        assert_eq!(v, vec![3, 4, 5, 6, 7]);
+        assert_eq!(calls.get(), 7);
+    }
+
+    #[test]
+    fn chained_conditions_nested_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let v: Vec<usize> = c![x, for x in y, for y in nested, if x % 2 == 0, if x > 3];
+        assert_eq!(v, vec![4, 6, 8]);
+    }
+
+    #[test]
+    fn enumerate_comprehension() {
+        let v = vec!['a', 'b', 'c'];
+        let indexed = c![(i, x), for (i, x) in enumerate v];
+        assert_eq!(indexed, vec![(0, 'a'), (1, 'b'), (2, 'c')]);
+    }
+
+    #[test]
+    fn enumerate_comprehension_indices() {
+        let indices = c![i, for (i, _) in enumerate 10..20];
+        assert_eq!(indices, (0..10).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn conditional_enumerate_comprehension() {
+        let v = [10, 20, 30, 40, 50];
+        let even_positions = c![x, for (i, x) in enumerate v.iter(), if i % 2 == 0];
+        assert_eq!(even_positions, vec![&10, &30, &50]);
+
+        let v = c![i * x, for (i, x) in enumerate 1..6, if i > 0, if x < 5];
+        assert_eq!(v, vec![2, 6, 12]);
+    }
+
+    #[test]
+    fn zip_comprehension() {
+        let pairs = c![(x, y), for x in 0..5, zip y in 'a'..='e'];
+        assert_eq!(pairs, vec![(0, 'a'), (1, 'b'), (2, 'c'), (3, 'd'), (4, 'e')]);
+    }
+
+    #[test]
+    fn zip_comprehension_shortest() {
+        let a = [1, 2, 3, 4, 5, 6];
+        let b = [10, 20, 30];
+        let sums = c![x + y, for x in a.iter(), zip y in b.iter()];
+        assert_eq!(sums.len(), a.len().min(b.len()));
+        assert_eq!(sums, vec![11, 22, 33]);
+
+        let sums = c![x + y, for x in b.iter(), zip y in a.iter()];
+        assert_eq!(sums, vec![11, 22, 33]);
+    }
+
+    #[test]
+    fn conditional_zip_comprehension() {
+        let pairs = c![(x, y), for x in 0..5, zip y in 'a'..='e', if x % 2 == 0, if y != 'c'];
+        assert_eq!(pairs, vec![(0, 'a'), (4, 'e')]);
+    }
+
+    #[test]
+    fn lazy_comprehension() {
+        let squares = ci![x * x, for x in 0..10];
+        assert_eq!(squares.collect::<Vec<usize>>(), c![x * x, for x in 0..10]);
+    }
+
+    #[test]
+    fn conditional_lazy_comprehension() {
+        let squares = ci![x * x, for x in 0..10, if x % 2 == 0];
+        assert_eq!(squares.collect::<Vec<usize>>(), c![x * x, for x in 0..10, if x % 2 == 0]);
+
+        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
+        let output: Vec<&i32> = ci![x, for x in vec.iter(), if *x >= 0i32].collect();
+        assert_eq!(output, vec![&0, &2, &4]);
+    }
+
+    #[test]
+    fn lazy_comprehension_is_lazy() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let first: Vec<u32> = ci![{ calls.set(calls.get() + 1); x }, for x in 0.., if x % 3 == 0]
+            .take(3)
+            .collect();
+
+        assert_eq!(first, vec![0, 3, 6]);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn capacity_comprehension() {
+        let squares: Vec<usize> = c![x*x, for x in 0..10; 10];
+        assert_eq!(squares, c![x*x, for x in 0..10]);
+        assert!(squares.capacity() >= 10);
+
+        let v = [1, 2, 3];
+        let doubled = c![x * 2, for x in v.iter(); v.len()];
+        assert_eq!(doubled, vec![2, 4, 6]);
+        assert!(doubled.capacity() >= v.len());
+    }
+
+    #[test]
+    fn conditional_capacity_comprehension() {
+        let squares = c![x*x, for x in 0..10, if x % 2 == 0; 5];
+        assert_eq!(squares, vec![0, 4, 16, 36, 64]);
+        assert!(squares.capacity() >= 5);
+    }
+
+    #[test]
+    fn nested_capacity_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let flat: Vec<usize> = c![x, for x in y, for y in nested; 9];
+        assert_eq!(flat, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(flat.capacity() >= 9);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_comprehension() {
+        let squares = par_c![x * x, for x in 0..1000u64];
+        assert_eq!(squares, c![x * x, for x in 0..1000u64]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn conditional_parallel_comprehension() {
+        let v: Vec<i32> = (-500..500).collect();
+        let output = par_c![x.abs(), for x in v.clone(), if x % 3 == 0];
+        assert_eq!(output, c![x.abs(), for x in v, if x % 3 == 0]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_hashmap_comprehension() {
+        let map = par_c!{key => key * key, for key in 0..100};
+        assert_eq!(map, c!{key => key * key, for key in 0..100});
+
+        let v: Vec<(&str, i32)> = vec![("one", 1), ("two", 2), ("three", 3)];
+        let map = par_c!{key => val, for (key, val) in v.clone(), if val != 2};
+        assert_eq!(map, c!{key => val, for (key, val) in v.clone(), if val != 2});
+
+        let map = par_c!{key => val, for (key, val) in v.clone(), if val != 2, if key.len() > 3};
+        assert_eq!(map, c!{key => val, for (key, val) in v, if val != 2, if key.len() > 3});
+    }
+
+    #[test]
+    fn try_comprehension() {
+        let strings = vec!["1", "2", "3"];
+        let parsed = try_c![s.parse::<i32>(), for s in strings];
+        assert_eq!(parsed, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn try_comprehension_first_error() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let parse = |s: &str| {
+            calls.set(calls.get() + 1);
+            s.parse::<i32>().map_err(|_| format!("bad input: {}", s))
+        };
+        let parsed = try_c![parse(s), for s in ["1", "x", "3", "y"].iter()];
+
+        assert_eq!(parsed, Err(String::from("bad input: x")));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn conditional_try_comprehension() {
+        let strings = vec!["1", "", "3", "four"];
+        let parsed = try_c![s.parse::<i32>(), for s in strings.clone(), if !s.is_empty(), if s.len() < 2];
+        assert_eq!(parsed, Ok(vec![1, 3]));
+
+        let parsed = try_c![s.parse::<i32>(), for s in strings, if !s.is_empty()];
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn option_comprehension() {
+        let strings = vec!["1", "two", "3", "", "5"];
+        let parsed: Vec<i32> = opt_c![s.parse().ok(), for s in strings];
+        assert_eq!(parsed, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn option_comprehension_all_none() {
+        let halves: Vec<i32> = opt_c![None, for _x in 0..10];
+        assert!(halves.is_empty());
+    }
+
+    #[test]
+    fn conditional_option_comprehension() {
+        let v = [Some(1), None, Some(-3), Some(4), None];
+        let positive = opt_c![*x, for x in v.iter(), if *x != Some(-3)];
+        assert_eq!(positive, vec![1, 4]);
+    }
+
+    #[test]
+    fn four_level_nested_comprehension() {
+        let tuples = c![(a, b, c, d), for d in 0..3, for c in 0..3, for b in 0..3, for a in 0..3];
+        assert_eq!(tuples.len(), 81);
+        assert_eq!(tuples[..4], [(0, 0, 0, 0), (0, 0, 0, 1), (0, 0, 0, 2), (0, 0, 1, 0)]);
+        assert_eq!(tuples[80], (2, 2, 2, 2));
+    }
+
+    #[test]
+    fn conditional_four_level_nested_comprehension() {
+        let increasing = c![(a, b, c, d), for d in c+1..5, for c in b+1..5, for b in a+1..5, for a in 0..5, if a + b + c + d < 8];
+        assert_eq!(increasing, vec![(0, 1, 2, 3), (0, 1, 2, 4)]);
+
+        let sums = c![a + b + c + d, for d in 0..3, for c in 0..3, for b in 0..3, for a in 0..3, if a == b, if c == d];
+        assert_eq!(sums, vec![0, 2, 4, 2, 4, 6, 4, 6, 8]);
+    }
+
+    #[test]
+    fn if_let_comprehension() {
+        let strings = vec!["1", "two", "3", "four"];
+        let parsed: Vec<i32> = c![n * 10, for s in strings, if let Ok(n) = s.parse::<i32>()];
+        assert_eq!(parsed, vec![10, 30]);
+    }
+
+    #[test]
+    fn if_let_tuple_pattern_comprehension() {
+        let v = [(Some(1), Some('a')), (None, Some('b')), (Some(3), None), (Some(4), Some('d'))];
+        let pairs = c![(n, ch), for x in v.iter(), if let (Some(n), Some(ch)) = *x];
+        assert_eq!(pairs, vec![(1, 'a'), (4, 'd')]);
+    }
+
+    #[test]
+    fn conditional_if_let_comprehension() {
+        let v = vec![Some(1), None, Some(2), Some(3)];
+        let odd = c![n, for x in v, if let Some(n) = x, if n % 2 == 1];
+        assert_eq!(odd, vec![1, 3]);
+    }
+
+    #[test]
+    fn if_let_after_if_comprehension() {
+        let v = vec![Some(1), None, Some(2), Some(3), Some(4)];
+        let picked = c![n, for x in v.clone(), if x != Some(2), if let Some(n) = x];
+        assert_eq!(picked, vec![1, 3, 4]);
+
+        let picked = c![n, for x in v, if x.is_some(), if let Some(n) = x, if n > 1, let m = n * 10, if m < 40];
+        assert_eq!(picked, vec![2, 3]);
+    }
+
+    #[test]
+    fn if_let_hashmap_comprehension() {
+        let v = vec!["one:1", "two", "three:3"];
+        let map = c!{key => val, for s in v, if let Some((key, val)) = s.split_once(':')};
+        let mut expected: HashMap<&str, &str> = HashMap::new();
+        expected.insert("one", "1");
+        expected.insert("three", "3");
+
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn block_expression_comprehension() {
+        fn expensive(x: i32) -> i32 {
+            x * 3
+        }
+
+        let v = c![{ let t = expensive(x); let u = t + 1; t * u }, for x in 0..4];
+        assert_eq!(v, vec![0, 12, 42, 90]);
+
+        let v = c![{ let t = expensive(x); t * t + t }, for x in 0..6, if x % 2 == 1];
+        assert_eq!(v, vec![12, 90, 240]);
+    }
+
+    #[derive(Clone, Copy)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Clone, Copy)]
+    struct Pair(i32, i32);
+
+    #[test]
+    fn struct_pattern_comprehension() {
+        let points = vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }, Point { x: -5, y: 6 }];
+        let sums = c![x + y, for Point { x, y } in points.clone()];
+        assert_eq!(sums, vec![3, 7, 1]);
+
+        let xs = c![x, for Point { x, .. } in points, if x > 0];
+        assert_eq!(xs, vec![1, 3]);
+    }
+
+    #[test]
+    fn tuple_struct_pattern_comprehension() {
+        let pairs = vec![Pair(1, 2), Pair(3, 4)];
+        let products = c![a * b, for Pair(a, b) in pairs];
+        assert_eq!(products, vec![2, 12]);
+    }
+
+    #[test]
+    fn tuple_pattern_comprehension() {
+        let pairs = vec![(1, 'a'), (2, 'b'), (3, 'c')];
+        let chars = c![ch, for (_, ch) in pairs.iter()];
+        assert_eq!(chars, vec![&'a', &'b', &'c']);
+
+        let chars = c![ch, for (n, ch) in pairs, if n % 2 == 1];
+        assert_eq!(chars, vec!['a', 'c']);
+    }
+
+    #[test]
+    fn nested_pattern_comprehension() {
+        let grid = vec![vec![Pair(1, 2), Pair(3, 4)], vec![Pair(5, 6)]];
+        let sums: Vec<i32> = c![a + b, for Pair(a, b) in row, for row in grid.clone()];
+        assert_eq!(sums, vec![3, 7, 11]);
+
+        let firsts: Vec<i32> = c![a, for Pair(a, _) in row, for row in grid, if a > 1];
+        assert_eq!(firsts, vec![3, 5]);
+    }
+
+    #[test]
+    fn pattern_set_comprehension() {
+        let points = vec![Point { x: 1, y: 1 }, Point { x: 2, y: 0 }, Point { x: 0, y: 3 }];
+        let sums = s![x + y, for Point { x, y } in points];
+        let expected: HashSet<i32> = [2, 3].iter().cloned().collect();
+        assert_eq!(sums, expected);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Number(i32),
+        Word(String),
+        Pair(i32, i32),
+    }
+
+    #[test]
+    fn enum_variant_comprehension() {
+        let tokens = vec![
+            Token::Number(1),
+            Token::Word(String::from("two")),
+            Token::Pair(3, 4),
+            Token::Number(5),
+        ];
+
+        let numbers = c![n, for t in tokens.iter(), if let Token::Number(n) = t];
+        assert_eq!(numbers, vec![&1, &5]);
+
+        let sums = c![a + b, for t in tokens.iter(), if let Token::Pair(a, b) = t];
+        assert_eq!(sums, vec![7]);
+
+        let words = c![w, for t in tokens, if let Token::Word(w) = t];
+        assert_eq!(words, vec![String::from("two")]);
+    }
+
+    #[test]
+    fn flat_map_comprehension() {
+        fn produce_items(x: u32) -> Vec<u32> {
+            vec![x; x as usize]
+        }
+
+        let outer = vec![1, 2, 3];
+        let items = c![item, for item in produce_items(x), for x in outer.clone()];
+        assert_eq!(items, vec![1, 2, 2, 3, 3, 3]);
+        assert_eq!(items, outer.into_iter().flat_map(produce_items).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn conditional_flat_map_comprehension() {
+        let words = vec!["ab", "cde"];
+        let chars = c![ch, for ch in word.chars(), for word in words, if ch != 'd'];
+        assert_eq!(chars, vec!['a', 'b', 'c', 'e']);
+    }
+
+    #[test]
+    fn group_comprehension() {
+        let words = vec!["apple", "bob", "avocado", "banana", "cherry"];
+        let groups = group_c!{word.chars().next().unwrap() => word, for word in words};
+
+        let mut expected: HashMap<char, Vec<&str>> = HashMap::new();
+        expected.insert('a', vec!["apple", "avocado"]);
+        expected.insert('b', vec!["bob", "banana"]);
+        expected.insert('c', vec!["cherry"]);
+
+        assert_eq!(groups, expected);
+    }
+
+    #[test]
+    fn group_tuple_comprehension() {
+        let v = vec![("even", 2), ("odd", 1), ("even", 4), ("odd", 3), ("even", 6)];
+        let groups = group_c!{key => val * 10, for (key, val) in v};
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["even"], vec![20, 40, 60]);
+        assert_eq!(groups["odd"], vec![10, 30]);
+    }
+
+    #[test]
+    fn frequency_comprehension() {
+        let v = vec![1, 2, 2, 3, 3, 3];
+        let counts = freq_c![x, for x in v];
+
+        let mut expected: HashMap<i32, usize> = HashMap::new();
+        expected.insert(1, 1);
+        expected.insert(2, 2);
+        expected.insert(3, 3);
+
+        assert_eq!(counts, expected);
+        assert!(!counts.contains_key(&4));
+    }
+
+    #[test]
+    fn conditional_frequency_comprehension() {
+        let text = "the cat and the hat and the bat";
+        let counts = freq_c![word, for word in text.split_whitespace(), if word.ends_with("at")];
+
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["cat"], 1);
+        assert_eq!(counts["hat"], 1);
+        assert_eq!(counts["bat"], 1);
+        assert!(!counts.contains_key("the"));
+
+        let counts = freq_c![x % 3, for x in 0..10, if x > 0];
+        assert_eq!(counts[&0], 3);
+        assert_eq!(counts[&1], 3);
+        assert_eq!(counts[&2], 3);
+    }
+
+    #[test]
+    fn partition_comprehension() {
+        let (pos, neg) = part_c![x * 10, for x in [-2, 1, -3, 4].iter().cloned(), if x > 0];
+        assert_eq!(pos, vec![10, 40]);
+        assert_eq!(neg, vec![-20, -30]);
+    }
+
+    #[test]
+    fn partition_comprehension_covers_all() {
+        let (small, large) = part_c![x * x, for x in 0..10, if x < 4];
+        assert!(small.iter().all(|x| *x < 16));
+        assert!(large.iter().all(|x| *x >= 16));
+
+        let mut all = small.clone();
+        all.extend(large);
+        assert_eq!(all, c![x * x, for x in 0..10]);
+    }
+
+    #[test]
+    fn sum_comprehension() {
+        let v = vec![1, 2, 3, 4];
+        assert_eq!(sum_c![x, for x in v.clone()], v.iter().sum::<i32>());
+        assert_eq!(sum_c![x * x, for x in 0..10], c![x * x, for x in 0..10].iter().sum::<i32>());
+        assert_eq!(sum_c![x, for x in 0..0], 0);
+    }
+
+    #[test]
+    fn float_sum_comprehension() {
+        let v = [0.5, 1.5, 2.0];
+        assert_eq!(sum_c![x * 2.0, for x in v.iter()], 8.0);
+    }
+
+    #[test]
+    fn conditional_sum_comprehension() {
+        let v = vec![-4, -2, 0, 2, 4];
+        let positive = sum_c![x, for x in v.clone(), if x > 0];
+        assert_eq!(positive, c![x, for x in v, if x > 0].iter().sum::<i32>());
+    }
+
+    #[test]
+    fn sum_comprehension_with_caller_sum_fn() {
+        fn sum(a: i32) -> i32 {
+            a * 10
+        }
+
+        assert_eq!(sum_c![sum(x), for x in 0..3], 30);
+    }
+
+    #[test]
+    fn product_comprehension_with_caller_product_fn() {
+        fn product(a: i32) -> i32 {
+            a + 1
+        }
+
+        assert_eq!(product_c![product(x), for x in 0..3], 6);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Money(u32);
+
+    impl std::iter::Sum for Money {
+        fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+            Money(iter.map(|m| m.0).sum())
+        }
+    }
+
+    #[test]
+    fn custom_sum_comprehension() {
+        let cents = vec![100, 250, 5];
+        assert_eq!(sum_c![Money(c), for c in cents.clone()], Money(355));
+        assert_eq!(sum_c![Money(c), for c in cents, if c > 10], Money(350));
+    }
+
+    #[test]
+    fn product_comprehension() {
+        fn factorial(n: u64) -> u64 {
+            product_c![x, for x in 1..=n]
+        }
+
+        assert_eq!(factorial(5), 120);
+        assert_eq!(factorial(10), 3_628_800);
+        assert_eq!(product_c![x * 2, for x in 1..4], c![x * 2, for x in 1..4].iter().product::<i32>());
+    }
+
+    #[test]
+    fn empty_product_comprehension() {
+        assert_eq!(product_c![x, for x in 0..0], 1);
+        assert_eq!(product_c![x, for x in 0..10, if x > 100], 1);
+    }
+
+    #[test]
+    fn conditional_product_comprehension() {
+        let v = [0.5, 2.0, -1.0, 4.0];
+        assert_eq!(product_c![x, for x in v.iter().cloned(), if x > 0.0], 4.0);
+    }
+
+    #[test]
+    fn count_comprehension() {
+        assert_eq!(count_c![for _x in 0..10], 10);
+        assert_eq!(count_c![for x in 0..10, if x % 2 == 0], c![x, for x in 0..10, if x % 2 == 0].len());
+        assert_eq!(count_c![for x in 0..10, if x > 100], 0);
+        assert_eq!(count_c![for x in Vec::<i32>::new(), if x > 100], 0);
+    }
+
+    #[test]
+    fn count_comprehension_with_expression() {
+        let v = [-4, -2, 0, 2, 4];
+        assert_eq!(count_c![x * 2, for x in v.iter()], c![x * 2, for x in v.iter()].len());
+        assert_eq!(count_c![x, for x in v.iter(), if *x >= 0], c![x, for x in v.iter(), if *x >= 0].len());
+
+        let words = vec!["a", "bb", "ccc", "dd"];
+        assert_eq!(count_c![w.len(), for w in words, if w.len() == 2, if w != "bb"], 1);
+    }
+
+    #[test]
+    fn max_min_comprehension() {
+        let v: Vec<i32> = vec![3, -7, 12, 0, 5];
+        assert_eq!(max_c![x, for x in v.clone()], v.iter().cloned().max());
+        assert_eq!(min_c![x, for x in v.clone()], v.iter().cloned().min());
+        assert_eq!(max_c![x.abs(), for x in v.iter()], Some(12));
+        assert_eq!(min_c![x.abs(), for x in v.iter()], Some(0));
+    }
+
+    #[test]
+    fn float_max_min_comprehension() {
+        let v = [1.5, -2.25, 8.0, 0.0];
+        assert_eq!(max_c![x, for x in v.iter().cloned()], Some(8.0));
+        assert_eq!(min_c![x * 2.0, for x in v.iter().cloned()], Some(-4.5));
+    }
+
+    #[test]
+    fn empty_max_min_comprehension() {
+        let v: Vec<i32> = vec![];
+        assert_eq!(max_c![x, for x in v.clone()], None);
+        assert_eq!(min_c![x, for x in v], None);
+        assert_eq!(max_c![x, for x in 0..10, if x > 10], None);
+    }
+
+    #[test]
+    fn conditional_max_min_comprehension() {
+        assert_eq!(max_c![x, for x in 0..100, if x % 7 == 0, if x % 2 == 1], Some(91));
+        assert_eq!(min_c![x, for x in 0..100, if x % 7 == 0, if x % 2 == 1], Some(7));
+    }
+
+    #[test]
+    fn max_min_by_key_comprehension() {
+        let words = vec!["pear", "fig", "banana", "kiwi", "plum"];
+        assert_eq!(max_c![w, for w in words.clone(), by w.len()], Some("banana"));
+        assert_eq!(min_c![w, for w in words.clone(), by w.len()], Some("fig"));
+
+        // ties resolve like Iterator::max_by_key and Iterator::min_by_key
+        assert_eq!(max_c![w, for w in words.clone(), if w.len() == 4, by w.len()], Some("plum"));
+        assert_eq!(min_c![w, for w in words.clone(), if w.len() == 4, by w.len()], Some("pear"));
+        assert_eq!(max_c![w.to_uppercase(), for w in words, if w.starts_with('p'), by w.len()], Some(String::from("PLUM")));
+    }
+
+    #[test]
+    fn fold_comprehension() {
+        assert_eq!(fold_c![0, acc, acc + x, for x in 1..=10], 55);
+        assert_eq!(fold_c![1, acc, acc * x, for x in 1..=5], 120);
+        assert_eq!(fold_c![7, acc, acc + x, for x in Vec::<i32>::new()], 7);
+    }
+
+    #[test]
+    fn string_fold_comprehension() {
+        let words = vec!["a", "b", "c"];
+        let joined = fold_c![String::new(), s, s + w, for w in words];
+        assert_eq!(joined, "abc");
+    }
+
+    #[test]
+    fn conditional_fold_comprehension() {
+        let evens = fold_c![0, acc, acc + x, for x in 1..=10, if x % 2 == 0];
+        assert_eq!(evens, 30);
+    }
+
+    #[test]
+    fn nested_fold_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6]];
+        let total = fold_c![0, acc, acc + x, for x in row, for row in nested];
+        assert_eq!(total, 21);
+    }
+
+    #[test]
+    fn custom_accumulator_fold_comprehension() {
+        // running (min, max) pair
+        let v = vec![3, -1, 8, 2];
+        let (lo, hi) = fold_c![(i32::MAX, i32::MIN), acc, (acc.0.min(x), acc.1.max(x)), for x in v];
+        assert_eq!((lo, hi), (-1, 8));
+    }
+
+    #[test]
+    fn any_all_comprehension() {
+        let v = [1, 3, 5, 6];
+        assert!(any_c![x % 2 == 0, for x in v.iter()]);
+        assert!(!all_c![x % 2 == 1, for x in v.iter()]);
+        assert!(all_c![*x > 0, for x in v.iter()]);
+        assert!(!any_c![*x > 10, for x in v.iter()]);
+    }
+
+    #[test]
+    fn empty_any_all_comprehension() {
+        let v: Vec<i32> = vec![];
+        assert!(!any_c![x > 0, for x in v.clone()]);
+        assert!(all_c![x > 0, for x in v]);
+    }
+
+    #[test]
+    fn nested_any_all_comprehension() {
+        let grid = [vec![1, 2], vec![3, 4]];
+        assert!(any_c![*x == 4, for x in row.iter(), for row in grid.iter()]);
+        assert!(all_c![*x < 5, for x in row.iter(), for row in grid.iter()]);
+        assert!(!all_c![*x < 4, for x in row.iter(), for row in grid.iter()]);
+    }
+
+    #[test]
+    fn any_all_comprehension_short_circuit() {
+        use std::cell::Cell;
+
+        let visited = Cell::new(0);
+        let counted = (0..100).inspect(|_| visited.set(visited.get() + 1));
+        assert!(any_c![x == 3, for x in counted]);
+        assert_eq!(visited.get(), 4);
+
+        visited.set(0);
+        let counted = (0..100).inspect(|_| visited.set(visited.get() + 1));
+        assert!(!all_c![x < 5, for x in counted]);
+        assert_eq!(visited.get(), 6);
+
+        visited.set(0);
+        let grid = [vec![1, 2], vec![3, 4], vec![5, 6]];
+        let rows = grid.iter().inspect(|_| visited.set(visited.get() + 1));
+        assert!(any_c![*x == 3, for x in row.iter(), for row in rows]);
+        assert_eq!(visited.get(), 2);
+    }
+
+    #[test]
+    fn find_comprehension() {
+        let v = vec![3, 8, 5, 10];
+        assert_eq!(find_c![x, for x in v.iter(), if *x % 2 == 0], Some(&8));
+        assert_eq!(find_c![x * 10, for x in v.iter(), if *x > 4], Some(80));
+        assert_eq!(find_c![x, for x in v.iter(), if *x > 100], None);
+        assert_eq!(find_c![x, for x in v], Some(3));
+    }
+
+    #[test]
+    fn find_comprehension_stops_early() {
+        let mut source = 0..10;
+        let found = find_c![x, for x in source.by_ref(), if x == 3];
+
+        assert_eq!(found, Some(3));
+        assert_eq!(source.next(), Some(4));
+    }
+
+    #[test]
+    fn find_comprehension_chained_conditions() {
+        let words = vec!["apple", "banana", "cherry", "blueberry"];
+        let found = find_c![w.to_uppercase(), for w in words, if w.starts_with('b'), if w.len() > 6];
+        assert_eq!(found, Some(String::from("BLUEBERRY")));
+    }
+
+    #[test]
+    fn position_comprehension() {
+        let v = [5, 3, 8, 1, 8];
+        assert_eq!(position_c![for x in v.iter(), if *x == 8], Some(2));
+        assert_eq!(position_c![for x in v.iter(), if *x < 6], Some(0));
+        assert_eq!(position_c![for x in v.iter(), if *x == 1], Some(3));
+        assert_eq!(position_c![for x in v.iter(), if *x > 3, if *x % 2 == 0], Some(2));
+    }
+
+    #[test]
+    fn position_comprehension_no_match() {
+        let empty: Vec<i32> = vec![];
+        assert_eq!(position_c![for x in empty, if x > 0], None);
+        assert_eq!(position_c![for x in 0..10, if x > 10], None);
+    }
+
+    #[test]
+    fn position_comprehension_repeated() {
+        let words = ["a", "bb", "ccc"];
+        for _ in 0..2 {
+            assert_eq!(position_c![for w in words.iter(), if w.len() == 3], Some(2));
+            assert_eq!(position_c![for w in words.iter(), if w.len() == 1], Some(0));
+        }
+    }
+
+    #[test]
+    fn flatten_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let flat: Vec<usize> = flatten_c![x, for x in nested.clone()];
+        assert_eq!(flat, c![x, for x in y, for y in nested]);
+    }
+
+    #[test]
+    fn flatten_options_comprehension() {
+        let v = vec![Some(1), None, Some(3)];
+        assert_eq!(flatten_c![x, for x in v], vec![1, 3]);
+    }
+
+    #[test]
+    fn flatten_ranges_comprehension() {
+        let ranges = vec![0..2, 5..7, 3..3];
+        assert_eq!(flatten_c![x * x, for x in ranges], vec![0, 1, 25, 36]);
+    }
+
+    #[test]
+    fn conditional_flatten_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        assert_eq!(flatten_c![x, for x in nested, if x % 2 == 0], vec![2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn windows_comprehension() {
+        let v: Vec<i32> = (1..=5).collect();
+        let sums = c![w.iter().sum::<i32>(), for w in v.windows(3)];
+        assert_eq!(sums, vec![6, 9, 12]);
+
+        let pairs = c![(w[0], w[1]), for w in v.windows(2), if w[0] % 2 == 0];
+        assert_eq!(pairs, vec![(2, 3), (4, 5)]);
+
+        // the source is only borrowed
+        assert_eq!(v.len(), 5);
+    }
+
+    #[test]
+    fn windows_hashmap_comprehension() {
+        let v = ['a', 'b', 'c', 'd'];
+        let next = c!{w[0] => w[1], for w in v.windows(2)};
+        let mut expected: HashMap<char, char> = HashMap::new();
+        expected.insert('a', 'b');
+        expected.insert('b', 'c');
+        expected.insert('c', 'd');
+
+        assert_eq!(next, expected);
+    }
+
+    #[test]
+    fn windows_comprehension_too_short() {
+        let v = [1];
+        let sums = c![w[0] + w[1], for w in v.windows(2)];
+        assert!(sums.is_empty());
+    }
+
+    #[test]
+    fn chunk_comprehension() {
+        let chunks = chunk_c![3, for x in 0..9];
+        assert_eq!(chunks, vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8]]);
+    }
+
+    #[test]
+    fn chunk_comprehension_remainder() {
+        let v = vec!['a', 'b', 'c', 'd', 'e'];
+        let chunks = chunk_c![2, for x in v];
+        assert_eq!(chunks, vec![vec!['a', 'b'], vec!['c', 'd'], vec!['e']]);
+        assert!(chunks.iter().all(|c| c.len() <= 2));
+    }
+
+    #[test]
+    fn empty_chunk_comprehension() {
+        let empty: Vec<i32> = vec![];
+        let chunks = chunk_c![4, for x in empty];
+        assert!(chunks.is_empty());
+    }
+
+    #[test]
+    fn conditional_chunk_comprehension() {
+        let chunks = chunk_c![2, for x in 0..10, if x % 3 != 0];
+        assert_eq!(chunks, vec![vec![1, 2], vec![4, 5], vec![7, 8]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk size must be non-zero")]
+    fn zero_size_chunk_comprehension() {
+        let _ = chunk_c![0, for x in 0..3];
+    }
+
+    #[test]
+    fn cartesian_comprehension() {
+        let pairs = cartesian_c![(x, y), x in 0..2, y in 0..2];
+        assert_eq!(pairs, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+
+        let cards = cartesian_c![format!("{}{}", rank, suit), rank in ['A', 'K'].iter(), suit in ['s', 'h'].iter()];
+        assert_eq!(cards, vec!["As", "Ah", "Ks", "Kh"]);
+    }
+
+    #[test]
+    fn three_way_cartesian_comprehension() {
+        let triples = cartesian_c![(x, y, z), x in 0..2, y in 0..2, z in 0..2];
+        assert_eq!(triples.len(), 8);
+        assert_eq!(triples[..3], [(0, 0, 0), (0, 0, 1), (0, 1, 0)]);
+        assert_eq!(triples[7], (1, 1, 1));
+    }
+
+    async fn square_later(x: i32) -> i32 {
+        x * x
+    }
+
+    #[test]
+    fn async_comprehension() {
+        let squares = futures::executor::block_on(ac![square_later(x).await, for x in 0..5]);
+        assert_eq!(squares, c![x * x, for x in 0..5]);
+    }
+
+    #[test]
+    fn conditional_async_comprehension() {
+        let items = vec![1, 2, 3, 4];
+        let future = async {
+            let odd = ac![square_later(x).await + 1, for x in items, if x % 2 == 1].await;
+            odd.len()
+        };
+        assert_eq!(futures::executor::block_on(future), 2);
+
+        let evens = futures::executor::block_on(ac![square_later(x).await, for x in 0..10, if x % 2 == 0]);
+        assert_eq!(evens, vec![0, 4, 16, 36, 64]);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn stream_comprehension() {
+        let numbers = futures::stream::iter(vec![-4, -2, 0, 2, 4]);
+        let doubled = futures::executor::block_on(stream_c![x * 2, for x in numbers]);
+        assert_eq!(doubled, c![x * 2, for x in [-4, -2, 0, 2, 4].iter()]);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn conditional_stream_comprehension() {
+        let pairs = futures::stream::iter(vec![("one", 1), ("two", 2), ("three", 3)]);
+        let names = futures::executor::block_on(stream_c![name, for (name, n) in pairs, if n % 2 == 1]);
+        assert_eq!(names, vec!["one", "three"]);
+    }
+
+    #[test]
+    fn question_mark_try_comprehension() {
+        use std::num::ParseIntError;
+
+        fn parse_all(lines: &[&str]) -> Result<Vec<i32>, ParseIntError> {
+            try_c![try l.trim().parse::<i32>()?, for l in lines]
+        }
+
+        assert_eq!(parse_all(&["1", " 2", "3 "]), Ok(vec![1, 2, 3]));
+        assert!(parse_all(&["1", "two", "3"]).is_err());
+        assert_eq!(parse_all(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn question_mark_nested_in_call_try_comprehension() {
+        use std::num::ParseIntError;
+
+        fn double(x: i32) -> i32 {
+            x * 2
+        }
+
+        fn doubled(lines: &[&str]) -> Result<Vec<i32>, ParseIntError> {
+            try_c![try double(l.parse::<i32>()?), for l in lines]
+        }
+
+        assert_eq!(doubled(&["1", "2"]), Ok(vec![2, 4]));
+        assert!(doubled(&["1", "x"]).is_err());
+
+        let sums: Result<Vec<i32>, ParseIntError> =
+            try_c![try [a.parse::<i32>()?, b.parse::<i32>()?].iter().sum(), for (a, b) in [("1", "2"), ("3", "4")].iter()];
+        assert_eq!(sums, Ok(vec![3, 7]));
+    }
+
+    #[test]
+    fn question_mark_try_comprehension_stops_early() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let parse = |s: &str| {
+            calls.set(calls.get() + 1);
+            s.parse::<i32>()
+        };
+        let parsed: Result<Vec<i32>, std::num::ParseIntError> =
+            try_c![try parse(s)? + parse(s)?, for s in ["1", "x", "3"].iter(), if !s.is_empty()];
+
+        assert!(parsed.is_err());
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn indexmap_comprehension() {
+        let keys = vec![9, 3, 7, 1, 5];
+        let map = oc!{key => key * key, for key in keys.clone()};
+
+        assert_eq!(map.keys().cloned().collect::<Vec<i32>>(), keys);
+        assert_eq!(map[&7], 49);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn conditional_indexmap_comprehension() {
+        let v: Vec<(&str, i32)> = vec![("three", 3), ("one", 1), ("four", 4), ("two", 2)];
+        let map = oc!{key => val, for (key, val) in v, if val != 1};
+
+        assert_eq!(map.keys().cloned().collect::<Vec<&str>>(), vec!["three", "four", "two"]);
+        assert_eq!(map.values().cloned().collect::<Vec<i32>>(), vec![3, 4, 2]);
+
+        let v = vec![("three", 3), ("one", 1), ("four", 4), ("two", 2)];
+        let map = oc!{key => val, for (key, val) in v, if val != 1, if key.len() > 3};
+        assert_eq!(map.keys().cloned().collect::<Vec<&str>>(), vec!["three", "four"]);
+    }
+
+    #[test]
+    fn heap_comprehension() {
+        let heap = heap_c![x % 5, for x in 0..12];
+        assert_eq!(heap.into_sorted_vec(), vec![0, 0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4]);
+
+        let mut heap = heap_c![x, for x in [3, 9, 1, 7].iter().cloned()];
+        assert_eq!(heap.pop(), Some(9));
+        assert_eq!(heap.pop(), Some(7));
+        assert_eq!(heap.pop(), Some(3));
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn conditional_heap_comprehension() {
+        let mut heap = heap_c![x * 10, for x in 0..10, if x % 4 != 0];
+        let mut popped = vec![];
+        while let Some(x) = heap.pop() {
+            popped.push(x);
+        }
+        assert_eq!(popped, vec![90, 70, 60, 50, 30, 20, 10]);
+    }
+
+    #[test]
+    fn take_while_comprehension() {
+        let squares = c![x * x, for x in 0.., while x < 5];
+        assert_eq!(squares, vec![0, 1, 4, 9, 16]);
+
+        let v = vec![2, 4, 5, 6, 8];
+        let evens = c![x, for x in v, while x % 2 == 0];
+        assert_eq!(evens, vec![2, 4]);
+    }
+
+    #[test]
+    fn take_while_comprehension_false_from_start() {
+        let none = c![x, for x in 10..20, while x < 10];
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn conditional_take_while_comprehension() {
+        let v = c![x, for x in 0.., while x < 20, if x % 3 == 0, if x % 2 == 0];
+        assert_eq!(v, vec![0, 6, 12, 18]);
+    }
+
+    #[test]
+    fn skip_while_comprehension() {
+        let v = vec![1, 3, 4, 5, 6];
+        let rest = c![x * 10, for x in v, skip_while x % 2 == 1];
+        assert_eq!(rest, vec![40, 50, 60]);
+    }
+
+    #[test]
+    fn skip_while_comprehension_always_true() {
+        let none = c![x, for x in 0..10, skip_while x < 100];
+        assert!(none.is_empty());
+
+        let all = c![x, for x in 0..5, skip_while x > 100];
+        assert_eq!(all, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn conditional_skip_while_comprehension() {
+        let v = c![x, for x in 0..20, skip_while x < 10, if x % 3 == 0];
+        assert_eq!(v, vec![12, 15, 18]);
+    }
+
+    #[test]
+    fn step_comprehension() {
+        let squares = c![x * x, for x in 0..20 step 3];
+        assert_eq!(squares, vec![0, 9, 36, 81, 144, 225, 324]);
+        assert_eq!(squares, c![x * x, for x in (0..20).step_by(3)]);
+
+        let n = 10;
+        let odd = c![x, for x in 1..n+1 step 2];
+        assert_eq!(odd, vec![1, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn conditional_step_comprehension() {
+        let v = c![x, for x in 0..=30 step 5, if x % 2 == 0];
+        assert_eq!(v, vec![0, 10, 20, 30]);
+        assert_eq!(v, c![x, for x in (0..=30).step_by(5), if x % 2 == 0]);
+    }
+
+    #[test]
+    fn chained_comprehension() {
+        let a = [1, 2, 3];
+        let b = [4, 5];
+        let v = c![x, for x in chain(a.iter().cloned(), b.iter().cloned())];
+        assert_eq!(v, vec![1, 2, 3, 4, 5]);
+        assert_eq!(v, c![x, for x in a.iter().cloned().chain(b.iter().cloned())]);
+
+        let v = c![x, for x in chain(0..2, 5..7, 10..12)];
+        assert_eq!(v, vec![0, 1, 5, 6, 10, 11]);
+    }
+
+    #[test]
+    fn conditional_chained_comprehension() {
+        let evens = c![x, for x in chain(0..5, 10..15), if x % 2 == 0];
+        assert_eq!(evens, vec![0, 2, 4, 10, 12, 14]);
+    }
+
+    #[test]
+    fn triple_zip_comprehension() {
+        let names = ["a", "b", "c"];
+        let ages = [30, 40, 50];
+        let v = c![(n, a, h), for n in names.iter().cloned(), zip a in ages.iter().cloned(), zip h in 170..173];
+        assert_eq!(v, vec![("a", 30, 170), ("b", 40, 171), ("c", 50, 172)]);
+        assert_eq!(v, c![(n, a, h), for ((n, a), h) in names.iter().cloned().zip(ages.iter().cloned()).zip(170..173)]);
+    }
+
+    #[test]
+    fn triple_zip_comprehension_shortest() {
+        let v = c![x + y + z, for x in 0..10, zip y in 0..3, zip z in 0..5];
+        assert_eq!(v, vec![0, 3, 6]);
+
+        let v = c![x * y * z, for x in 1..10, zip y in 1..10, zip z in 1..10, if x % 2 == 1];
+        assert_eq!(v, vec![1, 27, 125, 343, 729]);
+    }
+
+    #[test]
+    fn outer_filtered_nested_comprehension() {
+        let groups = vec![vec![1, 2], vec![3, 4, 5], vec![6], vec![7, 8, 9]];
+        let v: Vec<i32> = c![x, for x in g.iter().cloned(), if g.len() > 2, for g in &groups];
+        assert_eq!(v, vec![3, 4, 5, 7, 8, 9]);
+
+        let v: Vec<i32> = c![x, for x in g.iter().cloned(), if g.len() > 1, if g[0] > 1, for g in &groups, if x % 2 == 1];
+        assert_eq!(v, vec![3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn outer_filter_skips_inner_loop() {
+        let mut visited = 0;
+        let v = c![x, for x in { visited += 1; 0..n }, if n % 2 == 0, for n in 0..6];
+        assert_eq!(v, vec![0, 1, 0, 1, 2, 3]);
+        assert_eq!(visited, 3);
+    }
+
+    #[test]
+    fn nested_hashmap_comprehension() {
+        let rows = [vec![1, 2, 3], vec![4, 5]];
+        let map = c!{(i, j) => x * 10, for (j, &x) in row.iter().enumerate(), for (i, row) in rows.iter().enumerate()};
+
+        let mut expected: HashMap<(usize, usize), i32> = HashMap::new();
+        expected.insert((0, 0), 10);
+        expected.insert((0, 1), 20);
+        expected.insert((0, 2), 30);
+        expected.insert((1, 0), 40);
+        expected.insert((1, 1), 50);
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn nested_hashmap_comprehension_last_write_wins() {
+        let rows = vec![vec![1, 2], vec![3, 4]];
+        let map = c!{x % 2 => x, for &x in row, for row in &rows};
+
+        let mut expected: HashMap<i32, i32> = HashMap::new();
+        expected.insert(1, 3);
+        expected.insert(0, 4);
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn nested_hashmap_comprehension_order_matches_list() {
+        let order = c![(x, y), for y in 0..3, for x in 0..3];
+        let last: HashMap<i32, (i32, i32)> = c!{0 => (x, y), for y in 0..3, for x in 0..3};
+        assert_eq!(last[&0], *order.last().unwrap());
+
+        let cube = c!{(x, y, z) => x + y + z, for z in 0..2, for y in 0..2, for x in 0..2, if x != y};
+        assert_eq!(cube.len(), 4);
+        assert_eq!(cube[&(1, 0, 1)], 2);
+    }
+
+    #[test]
+    fn conditional_nested_hashmap_comprehension() {
+        let map = c!{(x, y) => x * y, for y in x..4, for x in 1..4, if (x + y) % 2 == 0};
+
+        let mut expected: HashMap<(i32, i32), i32> = HashMap::new();
+        expected.insert((1, 1), 1);
+        expected.insert((1, 3), 3);
+        expected.insert((2, 2), 4);
+        expected.insert((3, 3), 9);
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn conditional_group_comprehension() {
+        let words = ["apple", "bob", "avocado", "banana", "cherry", "ax"];
+        let groups = group_c!{word.len() => word, for &word in words.iter(), if word.starts_with('a') || word.starts_with('b')};
+
+        let mut expected: HashMap<usize, Vec<&str>> = HashMap::new();
+        expected.insert(5, vec!["apple"]);
+        expected.insert(3, vec!["bob"]);
+        expected.insert(7, vec!["avocado"]);
+        expected.insert(6, vec!["banana"]);
+        expected.insert(2, vec!["ax"]);
+        assert_eq!(groups, expected);
+
+        let groups = group_c!{x % 2 => x, for x in (0..20).rev(), if x % 3 == 0, if x > 5};
+        assert_eq!(groups[&0], vec![18, 12, 6]);
+        assert_eq!(groups[&1], vec![15, 9]);
+    }
+
+    #[test]
+    fn invert_comprehension() {
+        let mut map: HashMap<&str, i32> = HashMap::new();
+        map.insert("one", 1);
+        map.insert("two", 2);
+        map.insert("three", 3);
+
+        let inverted = invert_c!{for (k, v) in map.clone()};
+        let mut expected: HashMap<i32, &str> = HashMap::new();
+        expected.insert(1, "one");
+        expected.insert(2, "two");
+        expected.insert(3, "three");
+        assert_eq!(inverted, expected);
+        assert_eq!(inverted, c!{v => k, for (k, v) in map});
+    }
+
+    #[test]
+    fn invert_comprehension_last_write_wins() {
+        let pairs = vec![("a", 1), ("b", 2), ("c", 1)];
+        let inverted = invert_c!{for (k, v) in pairs};
+        assert_eq!(inverted.len(), 2);
+        assert_eq!(inverted[&1], "c");
+        assert_eq!(inverted[&2], "b");
+
+        let odd = invert_c!{for (k, v) in [("a", 1), ("b", 2), ("c", 3)], if v % 2 == 1};
+        assert_eq!(odd.len(), 2);
+        assert_eq!(odd[&3], "c");
+    }
+
+    #[test]
+    fn invert_group_comprehension() {
+        let pairs = vec![("a", 1), ("b", 2), ("c", 1), ("d", 1)];
+        let grouped = invert_group_c!{for (k, v) in pairs};
+
+        let mut expected: HashMap<i32, Vec<&str>> = HashMap::new();
+        expected.insert(1, vec!["a", "c", "d"]);
+        expected.insert(2, vec!["b"]);
+        assert_eq!(grouped, expected);
+    }
+
+    #[test]
+    fn zip_hashmap_comprehension() {
+        let keys = ["a", "b", "c"];
+        let values = [1, 2, 3];
+        let map = c!{k => v, for &k in keys.iter(), zip &v in values.iter()};
+
+        let mut expected: HashMap<&str, i32> = HashMap::new();
+        expected.insert("a", 1);
+        expected.insert("b", 2);
+        expected.insert("c", 3);
+        assert_eq!(map, expected);
+        assert_eq!(map, keys.iter().cloned().zip(values.iter().cloned()).collect::<HashMap<_, _>>());
+    }
+
+    #[test]
+    fn zip_hashmap_comprehension_shortest() {
+        let map = c!{k => v, for k in 0..10, zip v in "xyz".chars()};
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&2], 'z');
+
+        let map = c!{k => v, for k in 0..10, zip v in 10..20, if k % 3 == 0};
+        let mut expected: HashMap<i32, i32> = HashMap::new();
+        expected.insert(0, 10);
+        expected.insert(3, 13);
+        expected.insert(6, 16);
+        expected.insert(9, 19);
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn hist_comprehension() {
+        let text = "a rose is a rose is a rose";
+        let counts = hist_c!{word, for word in text.split_whitespace()};
+
+        let mut expected: HashMap<&str, usize> = HashMap::new();
+        expected.insert("a", 3);
+        expected.insert("rose", 3);
+        expected.insert("is", 2);
+        assert_eq!(counts, expected);
+
+        let long = hist_c!{word.len(), for word in text.split_whitespace(), if word.len() > 1};
+        assert_eq!(long[&4], 3);
+        assert_eq!(long[&2], 2);
+        assert!(!long.contains_key(&1));
+    }
+
+    #[test]
+    fn sorted_comprehension() {
+        let v = sorted_c![(x * 7) % 10, for x in 0..10];
+        assert_eq!(v, (0..10).collect::<Vec<i32>>());
+
+        let v = sorted_c![x * y, for x in 1..4, for y in (1..4).rev()];
+        assert_eq!(v, vec![1, 2, 2, 3, 3, 4, 6, 6, 9]);
+    }
+
+    #[test]
+    fn conditional_sorted_comprehension() {
+        let v = sorted_c![-x, for x in 0..10, if x % 3 == 0];
+        assert_eq!(v, vec![-9, -6, -3, 0]);
+    }
+
+    #[test]
+    fn sorted_by_comprehension_is_stable() {
+        let people = [("ann", 30), ("bob", 25), ("cid", 30), ("dee", 25)];
+        let v = sorted_by_c![name, for &(name, age) in people.iter(), by age];
+        assert_eq!(v, vec!["bob", "dee", "ann", "cid"]);
+
+        let v = sorted_by_c![name, for &(name, age) in people.iter(), if name != "bob", by std::cmp::Reverse(age)];
+        assert_eq!(v, vec!["ann", "cid", "dee"]);
+    }
+
+    #[test]
+    fn dedup_comprehension() {
+        let v = dedup_c![x, for &x in [1, 1, 2, 2, 2, 1, 3, 3].iter()];
+        assert_eq!(v, vec![1, 2, 1, 3]);
+
+        let v = dedup_c![x % 2, for x in 0..6, if x != 2];
+        assert_eq!(v, vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn dedup_by_key_comprehension() {
+        let v = dedup_by_key_c![x, for x in 0..10, by x / 4];
+        assert_eq!(v, vec![0, 4, 8]);
+
+        let v = dedup_by_key_c![x, for &x in [1, 3, 2, 5, 7, 4].iter(), by x % 2];
+        assert_eq!(v, vec![1, 2, 5, 4]);
+
+        let v = dedup_by_key_c![x, for &x in [1, 3, 2, 5, 7, 4].iter(), if x != 2, by x % 2];
+        assert_eq!(v, vec![1, 4]);
+    }
+
+    #[test]
+    fn unique_comprehension() {
+        let v = unique_c![x, for &x in [3, 1, 3, 2, 1, 4, 2].iter()];
+        assert_eq!(v, vec![3, 1, 2, 4]);
+
+        let words = "b a b c a d".split_whitespace();
+        let v = unique_c![w.to_uppercase(), for w in words];
+        assert_eq!(v, vec!["B", "A", "C", "D"]);
+    }
+
+    #[test]
+    fn conditional_unique_comprehension() {
+        let v = unique_c![x * y, for x in 1..4, for y in 1..4, if x != y];
+        assert_eq!(v, vec![2, 3, 6]);
+
+        let v = unique_c![x % 4, for x in 0..20, if x % 2 == 1];
+        assert_eq!(v, vec![1, 3]);
+    }
+
+    #[test]
+    fn unzip_comprehension() {
+        let words = ["apple", "bob", "cherry"];
+        let (lens, caps) = unzip_c![(w.len(), w.to_uppercase()), for w in words.iter()];
+        assert_eq!(lens, vec![5, 3, 6]);
+        assert_eq!(caps, vec!["APPLE", "BOB", "CHERRY"]);
+
+        let (xs, ys) = unzip_c![(x, y), for x in 0..2, for y in 0..2];
+        assert_eq!(xs.len(), ys.len());
+        assert_eq!(c![(x, y), for x in xs, zip y in ys], c![(x, y), for x in 0..2, for y in 0..2]);
+    }
+
+    #[test]
+    fn conditional_unzip_comprehension() {
+        let (xs, cubes) = unzip_c![(x, x * x * x), for x in 0..10, if x % 3 == 0];
+        assert_eq!(xs.len(), cubes.len());
+        let pairs = c![(x, c), for x in xs, zip c in cubes];
+        assert_eq!(pairs, vec![(0, 0), (3, 27), (6, 216), (9, 729)]);
+    }
+
+    #[test]
+    fn scan_comprehension() {
+        assert_eq!(scan_c![acc, 0, acc + x, for x in 1..=5], vec![1, 3, 6, 10, 15]);
+        assert_eq!(scan_c![acc, 1, acc * x, for x in 1..=5], vec![1, 2, 6, 24, 120]);
+
+        let words = scan_c![s, String::new(), s + w, for w in ["a", "b", "c"].iter()];
+        assert_eq!(words, vec!["a", "ab", "abc"]);
+
+        let running_max = scan_c![m, i32::MIN, m.max(x), for &x in [3, 1, 4, 1, 5, 9, 2].iter()];
+        assert_eq!(running_max, vec![3, 3, 4, 4, 5, 9, 9]);
+    }
+
+    #[test]
+    fn conditional_scan_comprehension() {
+        let v = scan_c![acc, 0, acc + x, for x in 0..10, if x % 2 == 1];
+        assert_eq!(v, vec![1, 4, 9, 16, 25]);
+        assert_eq!(scan_c![acc, 0, acc + x, for x in 0..10, if x > 100], Vec::<i32>::new());
+    }
+
+    #[test]
+    fn question_option_comprehension() {
+        fn parse_int(s: &str) -> Option<i32> {
+            s.parse().ok()
+        }
+
+        let strings = ["10", "x", "20", "", "-3"];
+        let v = c![? parse_int(s), for &s in strings.iter()];
+        assert_eq!(v, vec![10, 20, -3]);
+        assert_eq!(v, opt_c![parse_int(s), for &s in strings.iter()]);
+    }
+
+    #[test]
+    fn conditional_question_option_comprehension() {
+        let v = c![? x.checked_sub(3), for x in 0u32..8, if x % 2 == 0];
+        assert_eq!(v, vec![1, 3]);
+    }
+
+    #[test]
+    fn hashmap_entries_by_reference() {
+        let mut map: HashMap<&str, i32> = HashMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        map.insert("c", 3);
+
+        let mut doubled = c![v * 2, for (_, v) in map.iter()];
+        doubled.sort();
+        assert_eq!(doubled, vec![2, 4, 6]);
+
+        let mut keys = c![*k, for (k, &v) in &map, if v > 1];
+        keys.sort();
+        assert_eq!(keys, vec!["b", "c"]);
+
+        // the map is still usable after borrowing
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn hashmap_entries_by_mutable_reference() {
+        let mut map: HashMap<&str, i32> = HashMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+
+        let mut old = c![std::mem::replace(v, *v * 100), for (_, v) in map.iter_mut()];
+        old.sort();
+        assert_eq!(old, vec![1, 2]);
+        assert_eq!(map["a"], 100);
+        assert_eq!(map["b"], 200);
+    }
+
+    #[test]
+    fn hashmap_entries_by_value() {
+        let mut map: HashMap<String, Vec<i32>> = HashMap::new();
+        map.insert(String::from("x"), vec![1, 2]);
+        map.insert(String::from("y"), vec![3]);
+
+        let mut owned = c![(k, v.len()), for (k, v) in map.into_iter()];
+        owned.sort();
+        assert_eq!(owned, vec![(String::from("x"), 2), (String::from("y"), 1)]);
+
+        let mut map: HashMap<String, i32> = HashMap::new();
+        map.insert(String::from("z"), 26);
+        let owned: Vec<String> = c![k, for (k, _) in map];
+        assert_eq!(owned, vec![String::from("z")]);
+    }
+
+    #[test]
+    fn array_comprehension() {
+        let arr = c_arr![x, for x in 0..5; 5];
+        assert_eq!(arr, [0, 1, 2, 3, 4]);
+
+        let arr = c_arr![(x, y), for x in 0..2, for y in 0..2; 4];
+        assert_eq!(arr, [(0, 0), (1, 0), (0, 1), (1, 1)]);
+
+        let arr = c_arr![x.to_string(), for x in 0..10, if x % 4 == 0; 3];
+        assert_eq!(arr, [String::from("0"), String::from("4"), String::from("8")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 4 elements, the comprehension produced 5")]
+    fn array_comprehension_length_mismatch() {
+        let _ = c_arr![x, for x in 0..5; 4];
+    }
+
+    #[cfg(feature = "proc")]
+    #[test]
+    fn proc_comprehension_matches() {
+        assert_eq!(crate::proc_c![x * x, for x in 0..10, if x % 3 == 0], c![x * x, for x in 0..10, if x % 3 == 0]);
+        assert_eq!(crate::proc_c![(x, y), for x in 0..y, for y in 0..3], c![(x, y), for x in 0..y, for y in 0..3]);
+
+        let map: std::collections::HashMap<i32, i32> = crate::proc_c!{x => x * 2, for x in 0..4};
+        assert_eq!(map.len(), c!{x => x * 2, for x in 0..4}.len());
+        assert_eq!(map[&3], 6);
+    }
+
+    #[test]
+    fn hash_collections_match_the_public_aliases() {
+        let map: crate::HashMap<i32, i32> = c!{x => x * x, for x in 0..3};
+        assert_eq!(map[&2], 4);
+
+        let set: crate::HashSet<i32> = s![x % 2, for x in 0..5];
+        assert_eq!(set.len(), 2);
+
+        let groups: crate::HashMap<bool, Vec<i32>> = group_c!{x % 2 == 0 => x, for x in 0..4};
+        assert_eq!(groups[&true], vec![0, 2]);
+
+        let counts: crate::HashMap<char, usize> = freq_c![ch, for ch in "hello".chars()];
+        assert_eq!(counts[&'l'], 2);
+
+        let parsed: Result<crate::HashMap<&str, i32>, std::num::ParseIntError> =
+            try_c!{try s => s.parse::<i32>()?, for s in ["1", "2"]};
+        assert_eq!(parsed.unwrap()["2"], 2);
+    }
+
+    #[cfg(feature = "hashbrown")]
+    #[test]
+    fn hashbrown_collections() {
+        let map: hashbrown::HashMap<&str, usize> = c!{w => w.len(), for w in "a bb ccc".split(' ')};
+        assert_eq!(map["ccc"], 3);
+
+        let counts: hashbrown::HashMap<char, usize> = freq_c![ch, for ch in "hello".chars()];
+        assert_eq!(counts[&'l'], 2);
+
+        let v: Vec<i32> = c![x, for x in 0..3];
+        assert_eq!(v, vec![0, 1, 2]);
+    }
+
+    #[cfg(feature = "use_alloc")]
+    #[test]
+    fn alloc_collections() {
+        let v: alloc::vec::Vec<i32> = c![x * x, for x in 0..4, if x % 2 == 1];
+        assert_eq!(v, [1, 9]);
+
+        let map: hashbrown::HashMap<i32, i32> = c!{x => x * x, for x in 0..3};
+        assert_eq!(map[&2], 4);
+
+        let set: hashbrown::HashSet<i32> = s![x % 2, for x in 0..5];
+        assert_eq!(set.len(), 2);
+
+        let tree: alloc::collections::BTreeSet<i32> = bs![x % 3, for x in 0..9];
+        assert_eq!(tree.into_iter().collect::<Vec<i32>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn three_level_comprehension_order() {
+        // the first clause is the innermost loop, as with two clauses
+        let v = c![(a, b, c), for a in 0..2, for b in 0..2, for c in 0..2];
+        assert_eq!(v, vec![(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0), (0, 0, 1), (1, 0, 1), (0, 1, 1), (1, 1, 1)]);
+
+        let nested3 = vec![vec![vec![1, 2], vec![3]], vec![vec![4]]];
+        assert_eq!(c![x, for x in y, for y in z, for z in nested3.clone()], vec![1, 2, 3, 4]);
+        assert_eq!(c![x, for x in y, for y in z, for z in nested3, if x % 2 == 0], vec![2, 4]);
+
+        let v = c![(a, b, c), for c in b..3, for b in a..3, for a in 0..3];
+        assert_eq!(v.len(), 10);
+        assert_eq!(v, c![(a, b, c), for c in b..3, for b in a..3, for a in 0..3, if true]);
+    }
+
+    #[test]
+    fn three_level_order_matches_sibling_macros() {
+        let nested3 = [vec![vec![1, 2], vec![3]], vec![vec![4, 1]]];
+
+        let list = c![(x, y.len()), for x in y.iter().copied(), for y in z.iter(), for z in nested3.iter()];
+        assert_eq!(list, vec![(1, 2), (2, 2), (3, 1), (4, 2), (1, 2)]);
+
+        let options = opt_c![Some((x, y.len())), for x in y.iter().copied(), for y in z.iter(), for z in nested3.iter()];
+        assert_eq!(options, list);
+
+        let set = s![(x, y.len()), for x in y.iter().copied(), for y in z.iter(), for z in nested3.iter()];
+        assert_eq!(set, list.iter().copied().collect::<HashSet<_>>());
+        assert_eq!(set.len(), 4);
+    }
+
+    #[test]
+    fn five_level_comprehension() {
+        let tuples = c![(a, b, c, d, e), for e in 1..4, for d in 1..4, for c in 1..4, for b in 1..4, for a in 1..4];
+        assert_eq!(tuples.len(), 243);
+        assert_eq!(tuples[0], (1, 1, 1, 1, 1));
+        assert_eq!(tuples[1], (1, 1, 1, 1, 2));
+        assert_eq!(tuples[242], (3, 3, 3, 3, 3));
+
+        let increasing = c![(a, b, c, d, e), for e in d+1..8, for d in c+1..8, for c in b+1..8, for b in a+1..8, for a in 1..8];
+        assert_eq!(increasing.len(), 21);
+        assert_eq!(increasing[0], (1, 2, 3, 4, 5));
+    }
+
+    #[test]
+    fn conditional_five_level_comprehension() {
+        let v = c![(a, b, c, d, e), for e in 1..4, for d in 1..4, for c in 1..4, for b in 1..4, for a in 1..4, if a < b, if b < c];
+        assert_eq!(v.len(), 9);
+        assert!(v.iter().all(|&(a, b, c, _, _)| a < b && b < c));
+
+        let v = c![a + b + c + d + e, for e in 0..2, for d in 0..2, for c in 0..2, for b in 0..2, for a in 0..2, if a + b + c + d + e == 4];
+        assert_eq!(v, vec![4; 5]);
+    }
+
+    #[test]
+    fn six_level_comprehension() {
+        let v = c![[a, b, c, d, e, f], for f in 0..2, for e in 0..2, for d in 0..2, for c in 0..2, for b in 0..2, for a in 0..2];
+        assert_eq!(v.len(), 64);
+        assert_eq!(v[1], [0, 0, 0, 0, 0, 1]);
+        assert_eq!(v[32], [1, 0, 0, 0, 0, 0]);
+
+        let binary = c![32 * a + 16 * b + 8 * c + 4 * d + 2 * e + f, for f in 0..2, for e in 0..2, for d in 0..2, for c in 0..2, for b in 0..2, for a in 0..2];
+        assert_eq!(binary, (0..64).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn conditional_six_level_comprehension() {
+        let v = c![a + b + c + d + e + f, for f in 0..2, for e in 0..2, for d in 0..2, for c in 0..2, for b in 0..2, for a in 0..2, if a == b, if c == d, if e == f];
+        assert_eq!(v, vec![0, 2, 2, 4, 2, 4, 4, 6]);
+
+        let increasing = c![[a, b, c, d, e, f], for f in e+1..7, for e in d+1..7, for d in c+1..7, for c in b+1..7, for b in a+1..7, for a in 0..7, if a > 0];
+        assert_eq!(increasing, vec![[1, 2, 3, 4, 5, 6]]);
+    }
+
+    #[test]
+    fn outer_variables_do_not_clash() {
+        let r = [1];
+        let v = c![r[0] + x, for x in 0..3];
+        assert_eq!(v, vec![1, 2, 3]);
+
+        let map = 5;
+        let set = 7;
+        assert_eq!(c!{x => x + map, for x in 0..2}[&1], 6);
+        assert_eq!(s![x * set, for x in 0..2].len(), 2);
+
+        let r = 3;
+        let v = c![x * r, for x in 0..3, if x > 0];
+        assert_eq!(v, vec![3, 6]);
+    }
+
+    #[test]
+    fn nested_lazy_comprehension() {
+        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let flat: Vec<i32> = ci![x, for x in y.iter().cloned(), for y in &nested].collect();
+        assert_eq!(flat, c![x, for x in y.iter().cloned(), for y in &nested]);
+
+        let even: Vec<i32> = ci![x * 10, for x in y.iter().cloned(), for y in &nested, if x % 2 == 0].collect();
+        assert_eq!(even, vec![20, 40, 60, 80]);
+
+        let nested3 = vec![vec![vec![1, 2], vec![3]], vec![vec![4, 5]]];
+        let flat: Vec<i32> = ci![x, for x in y.iter().cloned(), for y in z, for z in &nested3].collect();
+        assert_eq!(flat, c![x, for x in y.iter().cloned(), for y in z, for z in &nested3]);
+
+        let n = 12;
+        let triples: Vec<(i32, i32, i32)> = ci![(a, b, c), for c in b..n, for b in a..n, for a in 1..n, if a * a + b * b == c * c].collect();
+        assert_eq!(triples, c![(a, b, c), for c in b..n, for b in a..n, for a in 1..n, if a * a + b * b == c * c]);
+    }
+
+    #[test]
+    fn nested_lazy_comprehension_is_lazy() {
+        use std::cell::Cell;
+
+        let calls = &Cell::new(0);
+        let first: Vec<(u64, u64)> = ci![{ calls.set(calls.get() + 1); (x, y) }, for x in 0..y, for y in 1.., if (x + y) % 2 == 0]
+            .take(3)
+            .collect();
+        assert_eq!(first, vec![(0, 2), (1, 3), (0, 4)]);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn let_else_comprehension() {
+        let inputs = ["4", "x", "16", "", "9"];
+        let roots = c![(n as f64).sqrt(), for s in inputs.iter(), let Ok(n) = s.parse::<u32>(), else continue];
+        assert_eq!(roots, vec![2.0, 4.0, 3.0]);
+
+        let tokens = vec![Token::Number(1), Token::Word(String::from("a")), Token::Number(2)];
+        let numbers = c![n, for t in tokens, let Token::Number(n) = t, else continue];
+        assert_eq!(numbers, vec![1, 2]);
+    }
+
+    #[test]
+    fn conditional_let_else_comprehension() {
+        let pairs = [Some((1, 'a')), None, Some((2, 'b')), Some((3, 'c'))];
+        let v = c![ch, for p in pairs.iter(), if p.is_some(), let Some((n, ch)) = p, else continue, if n % 2 == 1];
+        assert_eq!(v, vec![&'a', &'c']);
+
+        let nested = [Some(Some(1)), Some(None), None, Some(Some(4))];
+        let v = c![x, for o in nested.iter(), let Some(inner) = o, else continue, let Some(x) = inner, else continue];
+        assert_eq!(v, vec![&1, &4]);
+    }
+
+    #[test]
+    fn let_binding_comprehension() {
+        use std::cell::Cell;
+
+        let calls_a = Cell::new(0);
+        let calls_b = Cell::new(0);
+        let heavy_a = |x: i32| { calls_a.set(calls_a.get() + 1); x * 2 };
+        let heavy_b = |x: i32| { calls_b.set(calls_b.get() + 1); x + 100 };
+
+        let v = c![a + b + a * b, for x in 0..4, let a = heavy_a(x), let b = heavy_b(x)];
+        assert_eq!(v, vec![100, 305, 514, 727]);
+        assert_eq!(calls_a.get(), 4);
+        assert_eq!(calls_b.get(), 4);
+
+        let pairs = [(1, 2), (3, 4)];
+        let v = c![sum, for p in pairs.iter(), let &(a, b) = p, let sum = a + b];
+        assert_eq!(v, vec![3, 7]);
+    }
+
+    #[test]
+    fn conditional_let_binding_comprehension() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let f = |x: i32| { calls.set(calls.get() + 1); x * x - 10 };
+
+        let v = c![a, for x in 0..6, let a = f(x), if a > 0];
+        assert_eq!(v, vec![6, 15]);
+        assert_eq!(calls.get(), 6);
+
+        let v = c![a, for x in 0..10, if x % 2 == 0, let a = x * 3, if a > 10, let b = a + 1, if b % 4 == 1];
+        assert_eq!(v, vec![12, 24]);
+    }
+
+    #[test]
+    #[allow(unused_braces)]
+    fn match_expression_comprehension() {
+        let v = c![match x { 0 => "zero", _ => "other" }, for x in 0..3];
+        assert_eq!(v, vec!["zero", "other", "other"]);
+
+        let v = c![{ match x { 0 => "zero", _ => "other" } }, for x in 0..3];
+        assert_eq!(v, vec!["zero", "other", "other"]);
+    }
+
+    #[test]
+    fn conditional_match_expression_comprehension() {
+        let tokens = [Token::Number(1), Token::Word(String::from("a")), Token::Pair(2, 3)];
+        let v = c![match t { Token::Number(n) => *n, Token::Pair(a, b) => a * b, Token::Word(_) => 0 }, for t in tokens.iter(), if !matches!(t, Token::Word(_))];
+        assert_eq!(v, vec![1, 6]);
+
+        let v = c![(x, y), for y in match x { 0 => 0..1, _ => 0..x }, for x in 0..3];
+        assert_eq!(v, vec![(0, 0), (1, 0), (2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn format_comprehension() {
+        let v = c![format!("item_{}", x), for x in 0..5];
+        assert_eq!(v, vec!["item_0", "item_1", "item_2", "item_3", "item_4"]);
+
+        let v = c![format!("{}{}", x, y), for x in 'a'..'c', for y in 1..3];
+        assert_eq!(v, vec!["a1", "b1", "a2", "b2"]);
+
+        let map = c!{x => format!("{:02}", x), for x in 8..11};
+        assert_eq!(map[&9], "09");
+        assert_eq!(map[&10], "10");
+    }
+
+    #[test]
+    fn conditional_format_comprehension() {
+        let items = [Some(1), None, Some(3)];
+        let v = c![format!("{:?}", x), for x in items.iter(), if x.is_some()];
+        assert_eq!(v, vec!["Some(1)", "Some(3)"]);
+
+        let v = c![format!("{x}-{}", x * x), for x in 0..10, if x % 4 == 0, if x > 0];
+        assert_eq!(v, vec!["4-16", "8-64"]);
+    }
+
+    #[test]
+    fn string_comprehension() {
+        let text = String::from("alpha beta\n  gamma  \ndelta");
+
+        let lines: Vec<String> = c![line.trim().to_string(), for line in text.lines()];
+        assert_eq!(lines, vec!["alpha beta", "gamma", "delta"]);
+
+        let words: Vec<&str> = c![w, for w in text.split_whitespace()];
+        assert_eq!(words, vec!["alpha", "beta", "gamma", "delta"]);
+
+        let upper: Vec<char> = c![ch.to_ascii_uppercase(), for ch in "abc".chars()];
+        assert_eq!(upper, vec!['A', 'B', 'C']);
+
+        let bytes: Vec<u8> = c![b - b'0', for b in "1234".bytes()];
+        assert_eq!(bytes, vec![1, 2, 3, 4]);
+
+        let fields: Vec<i32> = c![f.parse().unwrap(), for f in "1,22,333".split(',')];
+        assert_eq!(fields, vec![1, 22, 333]);
+
+        // the source string is only borrowed
+        assert!(text.starts_with("alpha"));
+    }
+
+    #[test]
+    fn conditional_string_comprehension() {
+        let text = "one\n\n# comment\ntwo";
+        let lines = c![line, for line in text.lines(), if !line.is_empty(), if !line.starts_with('#')];
+        assert_eq!(lines, vec!["one", "two"]);
+
+        let vowels = c![ch, for ch in "comprehension".chars(), if "aeiou".contains(ch)];
+        assert_eq!(vowels.into_iter().collect::<String>(), "oeeio");
+
+        let lengths = c!{w => w.len(), for w in "a bb ccc".split(' ')};
+        assert_eq!(lengths["bb"], 2);
+    }
+
+    #[test]
+    fn conditional_comprehension_without_clone() {
+        #[derive(Debug, PartialEq)]
+        struct NoClone(i32);
+
+        let v = c![NoClone(x), for x in 0..6, if x % 2 == 0];
+        assert_eq!(v, vec![NoClone(0), NoClone(2), NoClone(4)]);
+
+        let owned = vec![NoClone(1), NoClone(2), NoClone(3)];
+        let v = c![n, for n in owned, if n.0 != 2];
+        assert_eq!(v, vec![NoClone(1), NoClone(3)]);
+
+        let words = vec![String::from("a"), String::from("bb")];
+        let v = c![w, for w in words, if w.len() > 1, if w.starts_with('b')];
+        assert_eq!(v, vec![String::from("bb")]);
+    }
+
+    #[test]
+    fn pattern_hashmap_comprehension() {
+        let points = vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }];
+        let map = c!{x => y, for Point { x, y } in points};
+
+        let mut expected: HashMap<i32, i32> = HashMap::new();
+        expected.insert(1, 2);
+        expected.insert(3, 4);
+        assert_eq!(map, expected);
+
+        let pairs = vec![Pair(1, 10), Pair(2, 20), Pair(3, 30)];
+        let map = c!{a => b, for Pair(a, b) in pairs, if a != 2};
+        let mut expected: HashMap<i32, i32> = HashMap::new();
+        expected.insert(1, 10);
+        expected.insert(3, 30);
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn enum_pattern_hashmap_comprehension() {
+        let tokens = vec![Token::Pair(1, 2), Token::Number(7), Token::Pair(3, 4)];
+        let map = c!{a => b, for t in tokens, if let Token::Pair(a, b) = t};
+
+        let mut expected: HashMap<i32, i32> = HashMap::new();
+        expected.insert(1, 2);
+        expected.insert(3, 4);
+        assert_eq!(map, expected);
+
+        let map = c!{k => k * 2, for k in 0..3};
+        assert_eq!(map.len(), 3);
+        let map = c!{k => k * 2, for k in 0..3, if k > 0};
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn c_group_comprehension() {
+        let words = ["apple", "bob", "avocado", "banana", "cherry"];
+        let groups = c_group!{word.chars().next().unwrap() => word, for &word in words.iter()};
+        assert_eq!(groups[&'a'], vec!["apple", "avocado"]);
+        assert_eq!(groups[&'b'], vec!["bob", "banana"]);
+        assert_eq!(groups[&'c'], vec!["cherry"]);
+
+        let parity = c_group!{x % 2 => x, for x in (0..10).rev()};
+        assert_eq!(parity[&0], vec![8, 6, 4, 2, 0]);
+        assert_eq!(parity[&1], vec![9, 7, 5, 3, 1]);
 
-    ($exp:expr, for $i:ident in $iter:expr, for $i2:ident in $iter2:expr, if $cond:expr) => (
-        {
-            let mut r = vec![];
-            for $i2 in $iter2 {
-                for $i in $iter {
-                    if $cond{
-                        r.push($exp);
-                    }
-                }
-            }
-            r
-        }
-    );
+        let small = c_group!{x % 2 => x * 10, for x in 0..10, if x < 4};
+        let mut expected: HashMap<i32, Vec<i32>> = HashMap::new();
+        expected.insert(0, vec![0, 20]);
+        expected.insert(1, vec![10, 30]);
+        assert_eq!(small, expected);
+    }
 
-    ($exp:expr, for $i:ident in $iter:expr, for $i2:ident in $iter2:expr, for $i3:ident in $iter3:expr, if $cond:expr) => (
-        {
-            let mut r = vec![];
-            for $i in $iter {
-                for $i2 in $iter2 {
-                    for $i3 in $iter3 {
-                        if $cond {
-                            r.push($exp);
-                        }
-                    }
-                }
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_map_comprehension() {
+        fn collatz_len(mut n: u64) -> u32 {
+            let mut steps = 0;
+            while n != 1 {
+                n = match n % 2 { 0 => n / 2, _ => 3 * n + 1 };
+                steps += 1;
             }
-            r
+            steps
         }
-    );
 
-    ($key:expr => $val:expr, for $p:pat in $iter:expr) => (
-        {
-            use std::collections::HashMap;
-            let mut map = HashMap::new();
-            for $p in $iter {
-                map.insert($key, $val);
-            }
-            map
+        let map = parallel_map_c!{n => collatz_len(n), for n in 1..2000u64};
+        assert_eq!(map, c!{n => collatz_len(n), for n in 1..2000u64});
+        assert_eq!(map[&27], 111);
+
+        let words = vec!["a", "bb", "ccc", "dddd"];
+        let map = parallel_map_c!{w => w.len(), for w in words.clone(), if w.len() % 2 == 0, if w != "bb"};
+        assert_eq!(map, c!{w => w.len(), for w in words, if w.len() == 4});
+    }
+
+    #[test]
+    fn struct_pattern_guard_comprehension() {
+        #[derive(Clone)]
+        struct Item {
+            field_a: i32,
+            field_b: i32,
         }
-    );
 
-    ($key:expr => $val:expr, for $p:pat in $iter:expr, if $cond:expr) => (
-        {
-            use std::collections::HashMap;
-            let mut map = HashMap::new();
-            for $p in $iter {
-                if $cond {
-                    map.insert($key, $val);
-                }
-            }
-            map
+        let items = vec![
+            Item { field_a: 1, field_b: 10 },
+            Item { field_a: -2, field_b: 20 },
+            Item { field_a: 3, field_b: -30 },
+        ];
+
+        let v = c![field_a + field_b, for Item { field_a, field_b } in items.clone(), if field_a > 0];
+        assert_eq!(v, vec![11, -27]);
+
+        let v = c![field_a * field_b, for Item { field_a, field_b } in items.clone(), if field_a > 0, if field_b > 0];
+        assert_eq!(v, vec![10]);
+
+        let v = c![(a, b), for &Item { field_a: a, field_b: b } in items.iter(), if a + b > 15];
+        assert_eq!(v, vec![(-2, 20)]);
+
+        let v = c![b, for (i, Item { field_b: b, .. }) in items.into_iter().enumerate(), if i != 1 && b < 0];
+        assert_eq!(v, vec![-30]);
+    }
+
+    #[test]
+    fn hashmap_key_evaluated_once() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let key = |x: i32| {
+            calls.set(calls.get() + 1);
+            x.to_string()
+        };
+
+        let map = c!{key(x) => x, for x in 0..5};
+        assert_eq!(map.len(), 5);
+        assert_eq!(calls.get(), 5);
+
+        calls.set(0);
+        let map = c!{key(x) => x * x, for x in 0..10, if x % 2 == 0};
+        assert_eq!(map["4"], 16);
+        assert_eq!(calls.get(), 5);
+
+        calls.set(0);
+        let map = c!{key(x + y) => (x, y), for y in 0..3, for x in 0..3, if x < y};
+        assert_eq!(map.len(), 3);
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let map = c!{key(k) => v, for k in 0..4, zip v in "abcd".chars(), if k != 2};
+        assert_eq!(map["3"], 'd');
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn hashmap_string_keys() {
+        let words = ["a", "bb", "a", "ccc"];
+        let map = c!{w.to_string() => w.len(), for w in words.iter(), if w.len() < 3};
+
+        let mut expected: HashMap<String, usize> = HashMap::new();
+        expected.insert(String::from("a"), 1);
+        expected.insert(String::from("bb"), 2);
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn read_dir_comprehension() {
+        use std::ffi::OsString;
+        use std::{fs, io};
+
+        let dir = std::env::temp_dir().join(format!("cute-read-dir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a.txt", "b.txt", "c.log"].iter() {
+            fs::write(dir.join(name), name).unwrap();
         }
-    );
 
-    ($key:expr => $val:expr, for $i:ident in $iter:expr) => (
-        {
-            use std::collections::HashMap;
-            let mut map = HashMap::new();
-            for $i in $iter {
-                map.insert($key, $val);
-            }
-            map
+        let mut names = c![entry.unwrap().file_name(), for entry in fs::read_dir(&dir).unwrap()];
+        names.sort();
+        assert_eq!(names, vec!["a.txt", "b.txt", "c.log"]);
+
+        let listed: io::Result<Vec<OsString>> = try_c![try entry?.file_name(), for entry in fs::read_dir(&dir)?];
+        let mut listed = listed.unwrap();
+        listed.sort();
+        assert_eq!(listed, names);
+
+        let logs: io::Result<Vec<OsString>> = try_c![entry.map(|e| e.file_name()), for entry in fs::read_dir(&dir).unwrap()];
+        assert_eq!(logs.unwrap().len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        let missing: io::Result<Vec<OsString>> = try_c![try entry?.file_name(), for entry in fs::read_dir(&dir)?];
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn outer_variable_in_inner_iterator() {
+        let triangle = c![x, for x in 0..y, for y in 1..=5];
+        assert_eq!(triangle, vec![0, 0, 1, 0, 1, 2, 0, 1, 2, 3, 0, 1, 2, 3, 4]);
+
+        let words = ["ab", "cde"];
+        let chars = c![ch, for ch in w.chars(), for w in words.iter(), if ch != 'd'];
+        assert_eq!(chars, vec!['a', 'b', 'c', 'e']);
+    }
+
+    #[test]
+    fn boxed_closures() {
+        let squares = c![Box::new(move || x * x) as Box<dyn Fn() -> i32>, for x in 0..5];
+        assert_eq!(squares.len(), 5);
+        for (x, square) in squares.iter().enumerate() {
+            assert_eq!(square(), (x * x) as i32);
         }
-    );
 
-    ($key:expr => $val:expr, for $i:ident in $iter:expr, if $cond:expr) => (
-        {
-            use std::collections::HashMap;
-            let mut map = HashMap::new();
-            for $i in $iter {
-                if $cond {
-                    map.insert($key, $val);
-                }
-            }
-            map
+        let adders = c![Box::new(move |y| x + y) as Box<dyn Fn(i32) -> i32>, for x in 0..3, if x != 1];
+        assert_eq!(c![f(10), for f in adders.iter()], vec![10, 12]);
+    }
+
+    #[test]
+    fn unsafe_block_expression() {
+        let data = [3, 1, 4, 1, 5];
+        let ptr = data.as_ptr();
+        let copied = c![unsafe { *ptr.add(i) }, for i in 0..data.len()];
+        assert_eq!(copied, data);
+
+        let odd = c![unsafe { *ptr.add(i) }, for i in 0..data.len(), if i % 2 == 1];
+        assert_eq!(odd, vec![1, 1]);
+    }
+
+    #[test]
+    fn counted_comprehension() {
+        for n in [0, 1, 7, 100].iter() {
+            let (v, count) = c_counted![x * 2, for x in 0..*n];
+            assert_eq!(count, v.len());
+            assert_eq!(count, *n);
         }
-    );
-}
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+        let (odd, count) = c_counted![x, for x in 0..10, if x % 2 == 1, if x > 3];
+        assert_eq!(odd, vec![5, 7, 9]);
+        assert_eq!(count, 3);
+
+        let (flat, count) = c_counted![x, for x in y, for y in [[1, 2], [3, 4]]];
+        assert_eq!(flat, vec![1, 2, 3, 4]);
+        assert_eq!(count, 4);
+    }
+
     #[test]
-    fn simple_comprehension() {
-        let squares: Vec<usize> = c![x*x, for x in 0..10];
-        assert_eq!(squares, vec![0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+    fn sorted_source() {
+        use crate::{sorted, sorted_by};
+
+        let v = [5, 3, 9, 1, 7];
+        assert_eq!(c![x, for x in sorted(v.iter().cloned())], vec![1, 3, 5, 7, 9]);
+        assert_eq!(c![x, for x in sorted(v), if x > 5], vec![7, 9]);
+
+        // the first element kept is the smallest one, whatever the input order
+        let mut seen = Vec::new();
+        let firsts = c![{ seen.push(x); x }, for x in sorted(v), if seen.is_empty()];
+        assert_eq!(firsts, vec![1]);
+
+        let words = ["pear", "fig", "banana"];
+        let by_len = c![w.len(), for w in sorted_by(words.iter(), |a, b| a.len().cmp(&b.len()))];
+        assert_eq!(by_len, vec![3, 4, 6]);
+        assert_eq!(c![*w, for w in sorted_by(words.iter(), |a, b| b.cmp(a))], vec!["pear", "fig", "banana"]);
     }
 
     #[test]
-    fn filter_comprehension() {
-        let squares = c![x*x, for x in 0..10, if x % 2 == 0];
-        assert_eq!(squares, vec![0, 4, 16, 36, 64]);
+    fn try_hashmap_comprehension() {
+        fn fallible_pairs<'a>(items: &[&'a str]) -> Vec<Result<(&'a str, i32), String>> {
+            c![
+                match item.parse::<i32>() {
+                    Ok(n) => Ok((*item, n)),
+                    Err(_) => Err(format!("bad item {}", item)),
+                },
+                for item in items.iter()
+            ]
+        }
+
+        let map = try_c!{k => v, for (k, v) in fallible_pairs(&["1", "2", "3"])}.unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("1", 1);
+        expected.insert("2", 2);
+        expected.insert("3", 3);
+        assert_eq!(map, expected);
+
+        let first = try_c!{k => v, for (k, v) in fallible_pairs(&["x", "2", "y"])};
+        assert_eq!(first, Err("bad item x".to_string()));
+
+        let middle = try_c!{k => v, for (k, v) in fallible_pairs(&["1", "x", "y"])};
+        assert_eq!(middle, Err("bad item x".to_string()));
+
+        let even = try_c!{k => v * v, for (k, v) in fallible_pairs(&["1", "2", "4"]), if v % 2 == 0};
+        assert_eq!(even.unwrap().len(), 2);
+
+        let parsed: Result<HashMap<&str, i32>, std::num::ParseIntError> =
+            try_c!{try s => s.parse::<i32>()?, for s in ["4", "5"].iter().copied()};
+        assert_eq!(parsed.unwrap()["5"], 5);
     }
 
     #[test]
-    fn simple_nested_comprehension() {
-        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
-        let flat: Vec<usize> = c![x, for x in y, for y in nested];
-        assert_eq!(flat, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    fn results_comprehension() {
+        let strs = ["4", "x", "6", "", "8"];
+        let results: Vec<Result<i32, std::num::ParseIntError>> = c![s.parse(), for s in strs.iter()];
+        assert_eq!(results.len(), 5);
+        assert_eq!(c![r.is_ok(), for r in results.iter()], vec![true, false, true, false, true]);
+
+        let (oks, errs) = partition_results_c![s.parse::<i32>(), for s in strs.iter()];
+        assert_eq!(oks, vec![4, 6, 8]);
+        assert_eq!(errs.len(), 2);
+
+        let (big, errs) = partition_results_c![s.parse::<i32>(), for s in strs.iter(), if !s.is_empty()];
+        assert_eq!(big, vec![4, 6, 8]);
+        assert_eq!(errs.len(), 1);
+
+        let (oks, errs): (Vec<u8>, Vec<String>) = partition_results_c![
+            if x < y { Ok(x * y) } else { Err(format!("{}>={}", x, y)) },
+            for x in 1..3, for y in 1..3
+        ];
+        assert_eq!(oks, vec![2]);
+        assert_eq!(errs, vec!["1>=1", "2>=1", "2>=2"]);
     }
 
     #[test]
-    fn filter_nested_comprehension() {
-        let nested = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
-        let even_flat: Vec<usize> = c![x, for x in y, for y in nested, if x % 2 == 0];
-        assert_eq!(even_flat, vec![2, 4, 6, 8]);
+    fn indexed_comprehension() {
+        let v = ["a", "b", "c"];
+        assert_eq!(indexed_c![*x, for x in v.iter()], vec![(0, "a"), (1, "b"), (2, "c")]);
+        assert_eq!(indexed_c![*x, for x in v.iter(), renumber], vec![(0, "a"), (1, "b"), (2, "c")]);
+        assert_eq!(indexed_c![x, for x in 0..0], vec![]);
+
+        let words = ["apple", "fig", "kiwi", "banana", "plum"];
+        let long = indexed_c![w.len(), for w in words.iter(), if w.len() > 4];
+        assert_eq!(long, vec![(0, 5), (3, 6)]);
+
+        let long = indexed_c![w.len(), for w in words.iter(), if w.len() > 4, renumber];
+        assert_eq!(long, vec![(0, 5), (1, 6)]);
+
+        let short = indexed_c![*w, for w in words.iter(), if w.len() < 5, if *w != "kiwi"];
+        assert_eq!(short, vec![(1, "fig"), (4, "plum")]);
     }
 
     #[test]
-    fn repeated_nested_comprehension() {
-        let n: i32 = 10;
-        let triples = c![(x,y, z), for x in 1..n+1, for y in x..n+1, for z in y..n+1, if x.pow(2) + y.pow(2) == z.pow(2)];
-        println!("{:?}", triples);
-        assert_eq!(triples, vec![(3, 4, 5), (6, 8, 10)]);
+    fn owned_hashmap_comprehension() {
+        let mut owned_map: HashMap<String, i32> = HashMap::new();
+        owned_map.insert("one".to_string(), 1);
+        owned_map.insert("two".to_string(), 2);
+
+        // moves the `String` keys out of the consumed map
+        let doubled = c!{k => v * 2, for (k, v) in owned_map};
+        let mut expected: HashMap<String, i32> = HashMap::new();
+        expected.insert("one".to_string(), 2);
+        expected.insert("two".to_string(), 4);
+        assert_eq!(doubled, expected);
+
+        let keys: HashMap<String, i32> = c!{k + "!" => v, for (k, v) in doubled.clone(), if v > 2};
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys["two!"], 4);
+
+        let mut names = c![k, for (k, _) in doubled];
+        names.sort();
+        assert_eq!(names, vec!["one", "two"]);
     }
 
     #[test]
-    fn iter_nested_comprehension() {
-        let x = c![(x, y), for x in 0..2u8, for y in vec!['a', 'b']];
-        assert_eq!(x, vec![(0, 'a'), (1, 'a'), (0, 'b'), (1, 'b')]);
+    fn while_let_comprehension() {
+        struct Countdown {
+            n: u32,
+        }
+
+        impl Countdown {
+            fn next(&mut self) -> Option<u32> {
+                if self.n == 0 {
+                    None
+                } else {
+                    self.n -= 1;
+                    Some(self.n + 1)
+                }
+            }
+        }
+
+        let mut source = Countdown { n: 5 };
+        assert_eq!(c![x * x, while let Some(x) = source.next()], vec![25, 16, 9, 4, 1]);
+        assert_eq!(source.next(), None);
+
+        let mut source = Countdown { n: 10 };
+        let odd = c![x, while let Some(x) = source.next(), if x % 2 == 1, if x > 3];
+        assert_eq!(odd, vec![9, 7, 5]);
+
+        let mut tokens = Countdown { n: 3 };
+        let pairs = c![(n, n > 1), while let Some(n @ 1..=3) = tokens.next()];
+        assert_eq!(pairs, vec![(3, true), (2, true), (1, false)]);
     }
 
     #[test]
-    fn vector_to_iter_comprehension() {
-        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
-        let output: Vec<i32> = c![x*2, for x in vec.iter()];
-        assert_eq!(output, vec![-8, -4, 0, 4, 8]);
+    fn pairwise_comprehension() {
+        assert_eq!(pairwise_c![b - a, for (a, b) in consecutive_pairs(1..=5)], vec![1, 1, 1, 1]);
+        assert!(pairwise_c![b - a, for (a, b) in consecutive_pairs(0..1)].is_empty());
+        assert!(pairwise_c![b - a, for (a, b) in consecutive_pairs(0..0)].is_empty());
+
+        let prices = [10, 12, 9, 9, 15];
+        let drops = pairwise_c![a - b, for (&a, &b) in consecutive_pairs(prices.iter()), if b < a];
+        assert_eq!(drops, vec![3]);
+
+        let words = vec!["a".to_string(), "bb".to_string(), "cc".to_string()];
+        let same_len = pairwise_c![format!("{}{}", a, b), for (a, b) in consecutive_pairs(words), if a.len() == b.len()];
+        assert_eq!(same_len, vec!["bbcc"]);
     }
 
     #[test]
-    fn filter_comprehension_two() {
-        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
-        let output: Vec<i32> = c![x, for x in vec.iter(), if *x >= 0i32];
-        assert_eq!(output, vec![0, 2, 4]);
+    fn ref_pattern_comprehension() {
+        let string_vec = vec!["one".to_string(), "three".to_string(), "four".to_string()];
+        assert_eq!(c![x.len(), for ref x in string_vec.clone()], vec![3, 5, 4]);
+        assert_eq!(c![x.len(), for ref x in string_vec.clone(), if x.starts_with('t')], vec![5]);
+
+        let pairs = vec![("a".to_string(), 1), ("b".to_string(), 2)];
+        let keys = c![k.clone(), for (ref k, n) in pairs.clone(), if n > 1];
+        assert_eq!(keys, vec!["b"]);
+
+        let map = c!{k.len() => n, for (ref k, n) in pairs};
+        assert_eq!(map[&1], 2);
+
+        let mut owned = vec![String::from("x")];
+        let _ = c![x.push('!'), for ref mut x in owned.iter_mut()];
+        assert_eq!(owned, vec!["x!"]);
     }
 
     #[test]
-    fn apply_function_comprehension() {
-        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
-        let output: Vec<i32> = c![x.abs(), for x in vec.iter()];
-        assert_eq!(output, vec![4, 2, 0, 2, 4]);
+    fn bounded_comprehension() {
+        assert_eq!(bounded_c![x, for x in 0..100, limit 5], vec![0, 1, 2, 3, 4]);
+        assert_eq!(bounded_c![x, for x in 0..3, limit 5], vec![0, 1, 2]);
+        assert_eq!(bounded_c![x, for x in 0.., limit 0].len(), 0);
+        assert_eq!(bounded_c![x * 2, for x in 0.., if x % 3 == 0, limit 4], vec![0, 6, 12, 18]);
+
+        // the source is not advanced past the last collected element
+        let mut source = 0..10;
+        let firsts = bounded_c![x, for x in source.by_ref(), limit 2];
+        assert_eq!(firsts, vec![0, 1]);
+        assert_eq!(source.next(), Some(2));
     }
 
     #[test]
-    fn apply_user_defined_function() {
-        fn square(x: i32) -> i32 {
-            x * x
+    fn counting_hashmap_comprehension() {
+        let text = "mississippi";
+        let chars = c!{ch => count, for ch in text.chars(), counting};
+        let mut expected: HashMap<char, usize> = HashMap::new();
+        for ch in text.chars() {
+            *expected.entry(ch).or_insert(0) += 1;
         }
+        assert_eq!(chars, expected);
+        assert_eq!(chars[&'s'], 4);
 
-        let vec: Vec<i32> = vec![-4, -2, 0, 2, 4];
-        let squares: Vec<i32> = c![square(x), for x in vec];
-        assert_eq!(squares, vec![16, 4, 0, 4, 16]);
+        let sentence = "the quick fox and the lazy dog and the cat";
+        let words = c!{w => count, for w in sentence.split(' '), counting};
+        assert_eq!(words["the"], 3);
+        assert_eq!(words["and"], 2);
+        assert_eq!(words["fox"], 1);
+        assert_eq!(words.values().sum::<usize>(), 10);
+
+        let long = c!{w.len() => count, for w in sentence.split(' '), if w.len() > 3, counting};
+        assert_eq!(long[&5], 1);
+        assert_eq!(long[&4], 1);
+        assert_eq!(long.len(), 2);
     }
 
     #[test]
-    fn hashmap_comprehension() {
-        let v = vec!["one", "two", "three"];
-        let map = c!{key => key.to_uppercase(), for key in v};
-        let mut expected: HashMap<&str, String> = HashMap::new();
-        expected.insert("one", String::from("ONE"));
-        expected.insert("two", String::from("TWO"));
-        expected.insert("three", String::from("THREE"));
+    fn else_comprehension() {
+        let words = ["apple", "Fig", "kiwi", "Pear"];
+        let v = c![w.to_uppercase(), for w in words.iter(), if w.len() > 3, else w.to_lowercase()];
+        assert_eq!(v.len(), words.len());
+        assert_eq!(v, vec!["APPLE", "fig", "KIWI", "PEAR"]);
 
-        assert_eq!(map, expected);
+        let v = c![w.len(), for w in words.iter(), if w.len() > 3, if w.starts_with(char::is_lowercase), else 0];
+        assert_eq!(v, vec![5, 0, 4, 0]);
+
+        let expr = c![if x > 1 { x } else { 0 }, for x in 0..4];
+        assert_eq!(expr, c![x, for x in 0..4, if x > 1, else 0]);
     }
 
     #[test]
-    fn hashmap_comprehension_two() {
-        let v = vec!["one", "two", "three"];
-        let map = c!{format!("{}-true", key) => key.to_uppercase(), for key in v};
-        let mut expected: HashMap<String, String> = HashMap::new();
-        expected.insert(String::from("one-true"), String::from("ONE"));
-        expected.insert(String::from("two-true"), String::from("TWO"));
-        expected.insert(String::from("three-true"), String::from("THREE"));
+    fn join_comprehension() {
+        assert_eq!(join_c![x.to_string(), for x in 0..5; ", "], "0, 1, 2, 3, 4");
+        assert_eq!(join_c![x.to_string(), for x in Vec::<i32>::new(); ", "], "");
+        assert_eq!(join_c![x.to_string(), for x in 0..1; ", "], "0");
 
-        assert_eq!(map, expected);
+        let words = ["alpha", "beta", "gamma", "delta"];
+        assert_eq!(join_c![*w, for w in words.iter(), if w.ends_with('a'), if w.len() == 5; "-"], "alpha-gamma-delta");
+        assert_eq!(join_c![w.to_uppercase(), for w in words.iter(), if w.contains('e'); ""], "BETADELTA");
+
+        let sep = String::from(" | ");
+        assert_eq!(join_c![format!("{}{}", x, y), for x in 0..2, for y in ["a", "b"]; &sep], "0a | 1a | 0b | 1b");
     }
 
     #[test]
-    fn hashmap_comprehension_three() {
-        let v: Vec<(String, i32)> = vec![
-            (String::from("one"), 1),
-            (String::from("two"), 2),
-            (String::from("three"), 3),
-        ];
-        let map = c!{key => val, for (key, val) in v};
+    fn mutate_comprehension() {
+        let mut ints = vec![1, -2, 3];
+        mutate_c![for x in ints.iter_mut(), do *x = *x * *x];
+        assert_eq!(ints, vec![1, 4, 9]);
 
-        let mut expected: HashMap<String, i32> = HashMap::new();
-        expected.insert(String::from("one"), 1);
-        expected.insert(String::from("two"), 2);
-        expected.insert(String::from("three"), 3);
+        let mut floats = [0.5, 1.5, 2.5];
+        mutate_c![for x in &mut floats, if *x > 1.0, do *x -= 1.0];
+        assert_eq!(floats, [0.5, 0.5, 1.5]);
 
-        assert_eq!(map, expected);
+        #[derive(Debug, PartialEq)]
+        struct Account {
+            name: &'static str,
+            balance: i64,
+        }
+
+        let mut accounts = vec![
+            Account { name: "a", balance: 10 },
+            Account { name: "b", balance: -5 },
+        ];
+        mutate_c![for acc in accounts.iter_mut(), if acc.balance < 0, do acc.balance = 0];
+        mutate_c![for Account { name, .. } in accounts.iter_mut(), do *name = if *name == "a" { "A" } else { "B" }];
+        assert_eq!(accounts, vec![Account { name: "A", balance: 10 }, Account { name: "B", balance: 0 }]);
     }
 
     #[test]
-    fn hashmap_tuple_comprehension() {
-        let v: Vec<(&str, i32)> = vec![("one", 1), ("two", 2), ("three", 3)];
-        let map = c!{key => val, for (key, val) in v};
+    fn option_flattening_comprehension() {
+        let options = [Some(1), None, Some(3), None, None, Some(6)];
+        let v = c![? *o, for o in options.iter()];
+        assert_eq!(v, vec![1, 3, 6]);
+        assert_eq!(v, c![*o, for o in options.iter()].into_iter().flatten().collect::<Vec<_>>());
+        assert_eq!(v, options.iter().filter_map(|o| *o).collect::<Vec<_>>());
 
-        let mut expected: HashMap<&str, i32> = HashMap::new();
-        expected.insert("one", 1);
-        expected.insert("two", 2);
-        expected.insert("three", 3);
+        let firsts = c![? w.chars().next(), for w in ["ab", "", "cd"].iter()];
+        assert_eq!(firsts, vec!['a', 'c']);
 
-        assert_eq!(map, expected);
+        let nested = opt_c![x.checked_sub(y), for x in 0u8..3, for y in 1u8..3];
+        assert_eq!(nested, vec![0, 1, 0]);
     }
 
     #[test]
-    fn conditional_hashmap_tuple_comprehension() {
-        let v: Vec<(&str, i32)> = vec![("one", 1), ("two", 2), ("three", 3)];
-        let map = c!{key => val, for (key, val) in v, if val == 1 || val == 2};
+    fn hashmap_value_transform() {
+        let mut prices: HashMap<&str, u32> = HashMap::new();
+        prices.insert("apple", 3);
+        prices.insert("pear", 5);
+        prices.insert("plum", 8);
 
-        let mut expected: HashMap<&str, i32> = HashMap::new();
-        expected.insert("one", 1);
-        expected.insert("two", 2);
+        // borrowed: the keys are `&&str`, dereferenced back to `&str`
+        let doubled = c!{*k => v * 2, for (k, v) in prices.iter()};
+        let mut expected: HashMap<&str, u32> = HashMap::new();
+        expected.insert("apple", 6);
+        expected.insert("pear", 10);
+        expected.insert("plum", 16);
+        assert_eq!(doubled, expected);
 
-        assert_eq!(map, expected);
+        // borrowed keys can also be kept as references into the original map
+        let refs: HashMap<&&str, String> = c!{k => v.to_string(), for (k, v) in &prices, if *v > 4};
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[&"plum"], "8");
+
+        let cheap_p = c!{k => v, for (k, v) in prices.clone(), if k.starts_with('p'), if v < 8};
+        assert_eq!(cheap_p.len(), 1);
+        assert_eq!(cheap_p["pear"], 5);
+
+        let owned: HashMap<String, f64> = c!{k.to_string() => v as f64, for (k, v) in expected};
+        let halved = c!{k => v / 2.0, for (k, v) in owned.clone(), if k.len() == 4 && v > 10.0};
+        assert_eq!(halved.len(), 1);
+        assert_eq!(halved["plum"], 8.0);
+
+        let keys = c!{k.clone() => k.len(), for (k, _) in owned.iter()};
+        let mut names = c![k, for (k, _) in keys];
+        names.sort();
+        assert_eq!(names, vec!["apple", "pear", "plum"]);
     }
 
     #[test]
-    fn hashmap_from_iter() {
-        let map = c!{*key => key*key, for key in vec![1,2].iter()};
-        let mut e: HashMap<i32, i32> = HashMap::new();
-        e.insert(1, 1);
-        e.insert(2, 4);
+    fn debug_comprehension() {
+        use std::cell::RefCell;
 
-        assert_eq!(map, e);
+        let seen: RefCell<Vec<i32>> = RefCell::new(Vec::new());
+        let pairs = [(1, 'a'), (2, 'b'), (3, 'c')];
+        let chars = debug_c![ch, for (n, ch) in pairs.iter().copied(), if n != 2, debug |p: &(i32, char)| seen.borrow_mut().push(p.0)];
+        assert_eq!(chars, vec!['a', 'c']);
+        if cfg!(debug_assertions) {
+            assert_eq!(seen.into_inner(), vec![1, 2, 3]);
+        } else {
+            assert!(seen.into_inner().is_empty());
+        }
     }
 
     #[test]
-    fn conditional_hashmap_from_iter() {
-        let map = c!{*key => key*key, for key in vec![1,2].iter(), if *key % 2 == 0};
-        let mut e: HashMap<i32, i32> = HashMap::new();
-        e.insert(2, 4);
-
-        assert_eq!(map, e);
+    fn debug_comprehension_default_printer() {
+        let v = [4, 8, 15, 16, 23, 42];
+        assert_eq!(debug_c![x / 2, for x in v.iter()], c![x / 2, for x in v.iter()]);
+        assert_eq!(debug_c![x, for x in v.iter(), if *x % 2 == 1], c![x, for x in v.iter(), if *x % 2 == 1]);
     }
 
     #[test]
-    fn hashmap_from_range() {
-        let map = c!{key => key*key, for key in 1..3};
-        let mut e: HashMap<i32, i32> = HashMap::new();
-        e.insert(1, 1);
-        e.insert(2, 4);
+    fn const_comprehension() {
+        const DIGITS: [u8; 3] = [1, 2, 3];
+        assert_eq!(const_c![d * 2, for d in DIGITS], vec![2, 4, 6]);
+        assert_eq!(const_c![*d, for d in &DIGITS, if *d != 2], vec![1, 3]);
 
-        assert_eq!(map, e);
+        let mut words = ["a", "b"];
+        let _ = const_c![*w = "z", for w in &mut words, if *w == "b"];
+        assert_eq!(words, ["a", "z"]);
     }
 
     #[test]
-    fn conditional_hashmap_from_range() {
-        let map = c!{key => key*key, for key in 1..6, if key % 2 == 1};
-        let mut e: HashMap<i32, i32> = HashMap::new();
-        e.insert(1, 1);
-        e.insert(3, 9);
-        e.insert(5, 25);
+    fn newtype_pattern_comprehension() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Meters(f64);
+        struct Labeled(&'static str, u32);
 
-        assert_eq!(map, e);
+        let measurements = vec![Meters(1.5), Meters(0.25), Meters(4.0)];
+        let doubled: Vec<f64> = c![x * 2.0, for Meters(x) in measurements.clone()];
+        assert_eq!(doubled, vec![3.0, 0.5, 8.0]);
+
+        let long = c![Meters(x), for &Meters(x) in measurements.iter(), if x >= 1.0];
+        assert_eq!(long, vec![Meters(1.5), Meters(4.0)]);
+
+        let totals = c!{name => n * 10, for Labeled(name, n) in [Labeled("a", 1), Labeled("b", 2)]};
+        assert_eq!(totals["b"], 20);
+
+        let pairs = c![(x, y), for Meters(x) in measurements.clone(), for Meters(y) in [Meters(1.0)], if x > y];
+        assert_eq!(pairs, vec![(1.5, 1.0), (4.0, 1.0)]);
     }
 }
@@ -0,0 +1,6 @@
+#[macro_use]
+extern crate cute;
+
+fn main() {
+    let _ = c![for x in 0..10];
+}
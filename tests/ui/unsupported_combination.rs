@@ -0,0 +1,7 @@
+#[macro_use]
+extern crate cute;
+
+fn main() {
+    let v = vec![Some(1), None];
+    let _ = c![n + y, for y in 0..2, for x in v, if let Some(n) = x];
+}